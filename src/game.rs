@@ -1,16 +1,29 @@
-use anyhow::Result;
-use either::Either::{self, Left, Right};
-use hashbrown::HashMap;
-use std::{collections::VecDeque, fmt::Debug, fs::File, hash::Hash, io::Write, ops::Deref};
-use rand::seq::SliceRandom;
+mod export;
+mod policy;
+mod rollout;
+mod selection;
+mod shared;
 
-fn uct(score: f32, visits: u32, total_visits: u32, c: f32) -> f32 {
-    if visits == 0 {
-        f32::INFINITY
-    } else {
-        score / (visits as f32) + c * ((total_visits as f32).ln() / (visits as f32)).sqrt()
-    }
-}
+use anyhow::{anyhow, Result};
+use hashbrown::{HashMap, HashSet};
+use std::{
+    collections::VecDeque,
+    fmt::{self, Debug},
+    fs::File,
+    hash::Hash,
+    io,
+    ops::{Deref, Range},
+    sync::{atomic::{AtomicU64, Ordering}, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+pub use export::DotOptions;
+pub use policy::{DirichletNoise, PolicyValue};
+pub use rollout::{Cutoff, EpsilonGreedy, Evaluator, RolloutPolicy, Softmax, Uniform};
+pub use selection::{ChildStats, Puct, SelectionPolicy, Thompson, Ucb1, Ucb1Tuned, UcbV, ValueBounds};
 
 #[derive(Debug, Clone, Copy)]
 pub enum MoveScore {
@@ -21,10 +34,7 @@ pub enum MoveScore {
 
 impl MoveScore {
     fn is_terminal(&self) -> bool {
-        match self {
-            MoveScore::Terminal(_) => true,
-            _ => false
-        }
+        matches!(self, MoveScore::Terminal(_))
     }
 
     fn score(&self) -> f32 {
@@ -39,17 +49,191 @@ pub trait Game: Clone + Debug {
     const IS_PERFECT_INFORMATION: bool;
 
     type Move: Default + Debug + Clone + PartialEq + Hash + Eq;
-    type GameState;
-    type Player: Clone + Debug;
+    type GameState: Clone;
+    type Player: Clone + Debug + PartialEq;
 
     fn possible_moves(&self) -> Vec<Self::Move>;
     fn place_move(&mut self, movement: Self::Move) -> Result<Self::GameState>;
     /// returns score used for backpropagation.
     /// none if state is not terminal
     fn score_state(&self, state: Self::GameState, player: Self::Player) -> MoveScore;
+    /// player who places the next move, for chance events the player the outcome is dealt to
+    fn current_player(&self) -> Self::Player;
+    /// every player in the game, each is scored with `score_state` after a move
+    fn players(&self) -> Vec<Self::Player>;
+
+    fn num_players(&self) -> usize {
+        self.players().len()
+    }
+
+    /// hash identifying the state, including the player to move. states with equal hashes share
+    /// their statistics when transpositions are enabled. should be cheap to update incrementally
+    /// (e.g. zobrist hashing), none disables transpositions for the state
+    fn state_hash(&self) -> Option<u64> {
+        None
+    }
+
+    /// relative weight of a legal move, used as its prior without a policy value provider and
+    /// to order moves for progressive widening. uniform by default
+    fn move_prior(&self, _movement: &Self::Move) -> f32 {
+        1f32
+    }
+
+    /// bounds of the scores a player can accumulate over a game, used to normalise values during
+    /// selection. none normalises by the values observed so far
+    fn value_bounds(&self) -> Option<ValueBounds> {
+        None
+    }
+
+    /// games drawing randomness inside `place_move` keep their own rng, the search reseeds every
+    /// simulated copy from its rng so searches are reproducible from the search seed
+    fn reseed<R: Rng + ?Sized>(&mut self, _rng: &mut R) {}
+
+    /// samples a game consistent with everything `observer` can see, hidden state such as
+    /// opponent hands is redrawn. used by information set search, defaults to a plain copy
+    fn determinize<R: Rng + ?Sized>(&self, _observer: Self::Player, _rng: &mut R) -> Self {
+        self.clone()
+    }
+
+    /// outcomes of a pending chance event (dice roll, card draw) with their probabilities.
+    /// the outcome is applied with `place_move`, empty when the next move is made by a player
+    fn chance_outcomes(&self) -> Vec<(Self::Move, f32)> {
+        Vec::new()
+    }
+}
+
+/// how outcomes of chance nodes are chosen during selection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChanceMode {
+    /// sample an outcome by its probability, outcomes are added to the tree as they are drawn
+    #[default]
+    Sample,
+    /// expand every outcome at once and pick the outcome visited least relative to its probability
+    Enumerate,
+}
+
+/// how the move is picked from the root children once a search is done
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum FinalMoveSelection {
+    /// child with the highest mean score
+    MaxChild,
+    /// child with the most visits
+    #[default]
+    RobustChild,
+    /// keeps searching for up to `extra_iterations` iterations until the child with the highest
+    /// mean is also the most visited, falls back to the most visited child
+    MaxRobustChild { extra_iterations: usize },
+    /// child with the highest lower confidence bound, mean - c / sqrt(visits)
+    SecureChild { c: f32 },
+    /// samples children with probability proportional to visits^(1 / temperature), used to
+    /// generate varied self play games
+    Temperature(f32),
+}
+
+/// upper bound on the size of the search tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeBudget {
+    Nodes(usize),
+    /// estimated bytes, as counted by `Mcts::memory_usage`
+    Bytes(usize),
 }
 
+/// what the search does once the tree budget is reached. the root is always expanded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BudgetAction {
+    /// leaves are no longer expanded, simulations roll out from the deepest node in the tree
+    #[default]
+    StopExpanding,
+    /// drops the subtrees of the least visited nodes and compacts the tree to half the budget
+    Prune,
+}
+
+/// game theoretic value of a state proven by the solver
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Proof {
+    /// the player, indexed as in `Game::players`, wins with best play
+    Win(usize),
+    Draw,
+}
+
+/// children a node may hold, k * visits^alpha
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressiveWidening {
+    pub k: f32,
+    pub alpha: f32,
+}
+
+impl Default for ProgressiveWidening {
+    fn default() -> Self {
+        Self { k: 1f32, alpha: 0.5 }
+    }
+}
+
+impl ProgressiveWidening {
+    /// number of children allowed after `visits` visits, at least one
+    fn limit(&self, visits: u32) -> usize {
+        (self.k * (visits.max(1) as f32).powf(self.alpha)).ceil().max(1f32) as usize
+    }
+}
+
+/// how per-player scores are turned into the value stored on a node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backup {
+    /// each node keeps the score of the player who moved into it, so every player maximises
+    /// their own score
+    #[default]
+    MaxN,
+    /// every opponent is assumed to play against the searching player, nodes of opponents keep
+    /// the negated score of the searching player
+    Paranoid,
+    /// nodes keep the score of the player who moved into it minus the mean score of the other
+    /// players, turning every game into a zero-sum one
+    Negamax,
+}
+
+/// node reached during selection along with the player who moved into it
 #[derive(Debug, Clone, Copy)]
+struct Step {
+    id: NodeId,
+    mover: usize,
+    /// the move into the node ended the game
+    terminal: bool,
+}
+
+/// nodes reached by one iteration, kept between iterations so selection reuses its buffers
+#[derive(Debug, Default)]
+struct Path {
+    steps: Vec<Step>,
+    /// scores of every player after the move into each step, in the order of `Game::players`
+    scores: Vec<f32>,
+}
+
+impl Path {
+    /// starts a new path at `root`, which is not scored
+    fn reset(&mut self, root: Step, players: usize) {
+        self.steps.clear();
+        self.steps.push(root);
+        self.scores.clear();
+        self.scores.resize(players, 0f32);
+    }
+
+    fn last(&self) -> Step {
+        *self.steps.last().unwrap()
+    }
+
+    fn scores(&self, i: usize) -> &[f32] {
+        let players = self.scores.len() / self.steps.len();
+        &self.scores[i * players..(i + 1) * players]
+    }
+}
+
+/// draws an outcome with its probability, fails when no outcome has a positive probability
+fn sample_outcome<'a, M, R: Rng + ?Sized>(outcomes: &'a [(M, f32)], rng: &mut R) -> Result<&'a (M, f32)> {
+    outcomes.choose_weighted(rng, |(_, p)| *p)
+        .map_err(|e| anyhow!("invalid chance outcome probabilities: {e}"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 struct NodeId(usize);
 impl Deref for NodeId {
     type Target = usize;
@@ -59,249 +243,1593 @@ impl Deref for NodeId {
     
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 struct MctsNode<Move: Default + Debug> {
     pub placement_move: Move,
-    pub score: f32,
-    pub visits: u32,
+    /// number of selections in which this node's move was legal
+    pub availability: u32,
+    /// whether the game had a pending chance event at this node
+    pub chance: bool,
+    /// index of the player who placed the move into this node, scores are from their view
+    pub player: usize,
+    /// `Game::state_hash` of the state reached by this node
+    pub hash: Option<u64>,
+    /// statistics of the state shared by every transposition, kept on the canonical node
+    pub state: StateStats,
+    /// all moves as first statistics, simulations where the mover played this move later on
+    pub amaf_score: f32,
+    pub amaf_visits: u32,
+    /// value of the state proven by the solver, kept on the canonical node
+    pub proof: Option<Proof>,
+}
+
+/// statistics of a game state summed over every path reaching it
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+struct StateStats {
+    score: f32,
+    score_sq: f32,
+    visits: u32,
+}
+
+impl<Move: Default + Debug> MctsNode<Move> {
+    fn new(placement_move: Move) -> Self {
+        Self {
+            placement_move,
+            availability: 0,
+            chance: false,
+            player: 0,
+            hash: None,
+            state: StateStats::default(),
+            amaf_score: 0f32,
+            amaf_visits: 0,
+            proof: None,
+        }
+    }
+
+    /// blends the mean of `stats` with the amaf mean, the amaf weight sqrt(k / (3n + k)) fades
+    /// out as the child collects visits of its own
+    fn rave_stats(&self, stats: ChildStats, equivalence: f32) -> ChildStats {
+        if self.amaf_visits == 0 || stats.visits == 0 {
+            return stats;
+        }
+        let visits = stats.visits as f32;
+        let beta = (equivalence / (3f32 * visits + equivalence)).sqrt();
+        let mean = (1f32 - beta) * stats.mean() + beta * self.amaf_score / (self.amaf_visits as f32);
+        ChildStats { score: mean * visits, score_sq: (stats.variance() + mean * mean) * visits, ..stats }
+    }
+}
+
+/// children list of a node, a range of `MctsTree::edges`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+struct ChildRange {
+    start: u32,
+    len: u32,
+}
+
+impl ChildRange {
+    fn range(self) -> Range<usize> {
+        // widened first, loaded trees can hold any pair of u32
+        self.start as usize..self.start as usize + self.len as usize
+    }
 }
 
+/// search tree, or a dag once transpositions are merged. nodes reaching the same state share
+/// the children list and state statistics of the first node that reached it
+#[derive(Serialize, Deserialize)]
 struct MctsTree<Move: Default + Debug> {
     nodes: Vec<MctsNode<Move>>,
-    children: Vec<Vec<NodeId>>,
+    /// selection statistics of every node, kept apart from the nodes so selection only reads
+    /// the statistics of the children. priors are the outcome probabilities below chance nodes
+    stats: Vec<ChildStats>,
+    /// children lists of every node back to back, so selection walks borrowed slices
+    edges: Vec<NodeId>,
+    /// starts of the unused blocks of `edges` left behind by growing lists, by their length
+    #[serde(skip)]
+    free_edges: HashMap<u32, Vec<u32>>,
+    children: Vec<ChildRange>,
+    /// node owning the children list of each node
+    canonical: Vec<NodeId>,
+    /// canonical node of every hashed state, rebuilt when a tree is loaded
+    #[serde(skip)]
+    transpositions: HashMap<u64, NodeId>,
+    /// smallest and largest value backed up into the tree, none before the first backup
+    #[serde(default)]
+    value_bounds: Option<ValueBounds>,
 }
 
 impl<Move: Default + Debug> MctsTree<Move> {
     fn new() -> Self {
         Self {
-            nodes: vec![MctsNode { placement_move: Move::default(), score: 0f32, visits: 0 }],
-            children: vec![Vec::new()],
+            nodes: vec![MctsNode::new(Move::default())],
+            stats: vec![ChildStats { prior: 1f32, ..Default::default() }],
+            edges: Vec::new(),
+            free_edges: HashMap::new(),
+            children: vec![ChildRange::default()],
+            canonical: vec![NodeId(0)],
+            transpositions: HashMap::new(),
+            value_bounds: None,
         }
     }
 
+    fn observe_value(&mut self, value: f32) {
+        let observed = ValueBounds::new(value, value);
+        self.value_bounds = Some(self.value_bounds.map_or(observed, |b| b.union(observed)));
+    }
+
     fn add_child(&mut self, node: NodeId, placement_move: Move) -> Option<NodeId> {
         if *node < self.nodes.len() {
             let id = NodeId(self.nodes.len());
-            self.children[*node].push(id);
-            self.nodes.push(MctsNode { placement_move, score: 0f32, visits: 0 });
-            self.children.push(Vec::new());
+            let owner = *self.canonical[*node];
+            let mut list = self.children[owner];
+            if list.range().end == self.edges.len() {
+                self.edges.push(id);
+            } else {
+                // a list that is not the last one moves into a block freed by a list of its new
+                // length, or to the end. its old slots are freed for the next list growing into them
+                let old = list.range();
+                match self.free_edges.get_mut(&(list.len + 1)).and_then(Vec::pop) {
+                    Some(start) => {
+                        self.edges.copy_within(old.clone(), start as usize);
+                        self.edges[start as usize + old.len()] = id;
+                        list.start = start;
+                    },
+                    None => {
+                        list.start = self.edges.len() as u32;
+                        self.edges.extend_from_within(old.clone());
+                        self.edges.push(id);
+                    },
+                }
+                if !old.is_empty() {
+                    self.free_edges.entry(old.len() as u32).or_default().push(old.start as u32);
+                }
+            }
+            list.len += 1;
+            self.children[owner] = list;
+            self.nodes.push(MctsNode::new(placement_move));
+            self.stats.push(ChildStats { prior: 1f32, ..Default::default() });
+            self.children.push(ChildRange::default());
+            self.canonical.push(id);
             Some(id)
         } else {
             None
         }
     }
 
-    fn children(&self, node: NodeId) -> Option<Vec<NodeId>> {
-        self.children.get(*node).cloned()
+    /// range of `edges` holding the children of the node
+    fn child_range(&self, node: NodeId) -> Range<usize> {
+        self.children[*self.canonical[*node]].range()
     }
 
-    fn node(&self, node: NodeId) -> Option<&MctsNode<Move>> {
-        self.nodes.get(*node)
+    fn child_ids(&self, node: NodeId) -> &[NodeId] {
+        &self.edges[self.child_range(node)]
+    }
+
+    /// merges the node with an earlier node reaching the same state, evicting the least visited
+    /// half of the table when it is full. returns the canonical node
+    fn register_state(&mut self, node: NodeId, hash: u64, capacity: usize) -> NodeId {
+        self.nodes[*node].hash = Some(hash);
+        if let Some(c) = self.transpositions.get(&hash).copied() {
+            if *c != *node && self.children[*node].len == 0 {
+                self.canonical[*node] = c;
+            }
+            return self.canonical[*node];
+        }
+        if self.transpositions.len() >= capacity.max(1) {
+            let mut entries = self.transpositions.iter()
+                .map(|(h, c)| (self.nodes[**c].state.visits, *h))
+                .collect::<Vec<_>>();
+            // ties broken by hash so eviction does not depend on the table's iteration order
+            let half = entries.len().div_ceil(2);
+            entries.select_nth_unstable(half - 1);
+            for (_, h) in &entries[..half] {
+                self.transpositions.remove(h);
+            }
+        }
+        self.transpositions.insert(hash, node);
+        node
     }
 
-    fn len(&self) -> usize {
-        self.nodes.len()
+    /// selection statistics of a child, the mean comes from the state shared by all transpositions
+    /// while the visits are those of the edge into the child
+    fn transposed_stats(&self, node: NodeId) -> ChildStats {
+        let n = self.stats[*node];
+        let state = self.nodes[*self.canonical[*node]].state;
+        if state.visits == 0 {
+            return n;
+        }
+        let visits = n.visits as f32 / state.visits as f32;
+        ChildStats { score: state.score * visits, score_sq: state.score_sq * visits, ..n }
     }
 
-    fn dump(&self) {
-        let mut f = File::create("./out.dot").unwrap();
-        f.write("digraph G {overlap=\"scalexy;\"".as_bytes()).unwrap();
-        let mut queue = VecDeque::from([0usize]);
-        while let Some(parent) = queue.pop_front() {
-            if let Some(children) = self.children.get(parent) {
-                for child in children {
-                    let child = **child;
-                    let child_stats = &self.nodes[child];
-                    if child_stats.visits > 0 {
-                        f.write(format!("{parent}->{child};").as_bytes()).unwrap();
-                        f.write(format!("{child} [label=<{child}<br/>move={:?}<br/>score={}<br/>visits={}>];", child_stats.placement_move, child_stats.score, child_stats.visits).as_bytes()).unwrap();
-                    }
-                    queue.push_back(child);
+    /// estimated bytes taken by each node, including its slot in the parent's children list
+    fn node_bytes() -> usize {
+        size_of::<MctsNode<Move>>() + size_of::<ChildStats>() + size_of::<ChildRange>() + 2 * size_of::<NodeId>()
+    }
+
+    /// estimated bytes held by the tree, from the number of nodes rather than allocated capacity
+    fn memory_usage(&self) -> usize {
+        let node = size_of::<MctsNode<Move>>() + size_of::<ChildStats>() + size_of::<ChildRange>() + size_of::<NodeId>();
+        self.nodes.len() * node + self.edges.len() * size_of::<NodeId>() + self.transpositions.len() * size_of::<(u64, NodeId)>()
+    }
+
+    /// makes `node` the new root, dropping every node outside its subtree and compacting the arena
+    fn reroot(&mut self, node: NodeId) {
+        self.compact(node, 0);
+    }
+
+    /// fewest visits a node needs to keep its children so that at most `target` nodes are left.
+    /// the children of the root are always kept
+    fn prune_threshold(&self, root: NodeId, target: usize) -> u32 {
+        let mut expanded = self.canonical.iter()
+            .enumerate()
+            .filter(|(i, c)| ***c == *i && *i != *root && self.children[*i].len > 0)
+            .map(|(i, _)| (self.stats[i].visits, self.children[i].len as usize))
+            .collect::<Vec<_>>();
+        expanded.sort_unstable_by_key(|(visits, _)| std::cmp::Reverse(*visits));
+        let mut kept = 1 + self.child_ids(root).len();
+        // nodes with equal visits are kept or pruned together
+        for group in expanded.chunk_by(|a, b| a.0 == b.0) {
+            let children = group.iter().map(|(_, len)| len).sum::<usize>();
+            if kept + children > target {
+                return group[0].0 + 1;
+            }
+            kept += children;
+        }
+        0
+    }
+
+    /// makes `node` the root and drops the children of every other node visited fewer than
+    /// `min_visits` times, along with everything outside the subtree of `node`
+    fn compact(&mut self, node: NodeId, min_visits: u32) {
+        let mut nodes = Vec::new();
+        let mut stats = Vec::new();
+        let mut edges = Vec::new();
+        let mut children = Vec::new();
+        let mut canonical = Vec::new();
+        // new node owning each shared children list, the first node reaching a list takes it over
+        let mut owners = HashMap::new();
+        let mut queue = VecDeque::from([node]);
+        while let Some(old) = queue.pop_front() {
+            let id = NodeId(nodes.len());
+            let old_canonical = self.canonical[*old];
+            let (state, visits) = (self.nodes[*old_canonical].state, self.stats[*old_canonical].visits);
+            let mut n = std::mem::replace(&mut self.nodes[*old], MctsNode::new(Move::default()));
+            if let Some(owner) = owners.get(&*old_canonical) {
+                canonical.push(*owner);
+                children.push(ChildRange::default());
+            } else {
+                owners.insert(*old_canonical, id);
+                canonical.push(id);
+                n.state = state;
+                if old != node && visits < min_visits {
+                    // pruned nodes become leaves again, keeping their own statistics
+                    children.push(ChildRange::default());
+                } else {
+                    // children are assigned ids in the order they are queued
+                    let first_child = nodes.len() + queue.len() + 1;
+                    let old_children = &self.edges[self.children[*old_canonical].range()];
+                    children.push(ChildRange { start: edges.len() as u32, len: old_children.len() as u32 });
+                    edges.extend((first_child..first_child + old_children.len()).map(NodeId));
+                    queue.extend(old_children.iter().copied());
                 }
             }
+            nodes.push(n);
+            stats.push(self.stats[*old]);
         }
-        f.write("}".as_bytes()).unwrap();
-        f.flush().unwrap();
+        self.nodes = nodes;
+        self.stats = stats;
+        self.edges = edges;
+        self.free_edges.clear();
+        self.children = children;
+        self.canonical = canonical;
+        self.index_transpositions();
     }
 
+    /// rebuilds the transposition table from the canonical nodes
+    fn index_transpositions(&mut self) {
+        self.transpositions = self.nodes.iter()
+            .enumerate()
+            .filter(|(i, n)| *self.canonical[*i] == *i && n.hash.is_some())
+            .map(|(i, n)| (n.hash.unwrap(), NodeId(i)))
+            .collect();
+    }
+
+}
+
+/// cooperatively stops a running search from another thread
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle(Arc<StopState>);
+
+#[derive(Debug, Default)]
+struct StopState {
+    /// number of searches that have ended, the running or next search belongs to this generation
+    generation: AtomicU64,
+    /// one past the latest generation a stop was requested for
+    stopped: AtomicU64,
+}
+
+impl StopHandle {
+    /// stops the running search, or the next one if none is running
+    pub fn stop(&self) {
+        let generation = self.0.generation.load(Ordering::Relaxed);
+        self.0.stopped.fetch_max(generation + 1, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.stopped.load(Ordering::Relaxed) > self.0.generation.load(Ordering::Relaxed)
+    }
+
+    /// ends the current generation, stops requested for it no longer apply
+    fn finish(&self) {
+        self.0.generation.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// reasons a search could not pick a move
+#[derive(Debug)]
+pub enum MctsError {
+    /// the searched game has no legal moves
+    NoLegalMoves,
+    /// a game method failed during a simulation and failures are not retried
+    Game(anyhow::Error),
+    /// more simulations failed than the retry budget allows, `last` is the latest failure
+    RetriesExhausted { failed: usize, last: anyhow::Error },
+}
+
+impl fmt::Display for MctsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MctsError::NoLegalMoves => write!(f, "searched game has no legal moves"),
+            MctsError::Game(e) => write!(f, "simulation failed: {e}"),
+            MctsError::RetriesExhausted { failed, last } => write!(f, "{failed} simulations failed, last error: {last}"),
+        }
+    }
+}
+
+impl std::error::Error for MctsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MctsError::NoLegalMoves => None,
+            MctsError::Game(e) | MctsError::RetriesExhausted { last: e, .. } => Some(e.as_ref()),
+        }
+    }
+}
+
+/// statistics of a root child after a search
+#[derive(Debug, Clone)]
+pub struct RootChildReport<Move> {
+    pub placement_move: Move,
+    pub visits: u32,
+    pub mean: f32,
+    /// value the selection policy gives the child
+    pub uct: f32,
+    pub proof: Option<Proof>,
+}
+
+/// summary of the last search, for logging decisions and analysing games
+#[derive(Debug, Clone)]
+pub struct SearchReport<Move> {
+    /// child picked by the final move selection, the most visited child when moves are sampled
+    pub best_move: Move,
+    pub children: Vec<RootChildReport<Move>>,
+    /// expected line of play, following the most visited child from the root
+    pub principal_variation: Vec<Move>,
+    /// value of the root position proven by the solver
+    pub proof: Option<Proof>,
+    pub nodes: usize,
+    /// estimated bytes held by the tree, see `Mcts::memory_usage`
+    pub memory_usage: usize,
+    pub max_depth: usize,
+    pub iterations: usize,
+    pub failed_simulations: usize,
+    pub elapsed: Duration,
+}
+
+/// counters of a finished search
+#[derive(Debug, Clone, Copy, Default)]
+struct SearchCounts {
+    iterations: usize,
+    failed: usize,
+    elapsed: Duration,
+}
+
+#[derive(Clone)]
+struct SearchLimit {
+    iterations: usize,
+    deadline: Option<Instant>,
+    stop: StopHandle,
+}
+
+impl SearchLimit {
+    fn proceed(&self, completed: usize) -> bool {
+        completed < self.iterations
+            && !self.stop.is_stopped()
+            && self.deadline.is_none_or(|d| Instant::now() < d)
+    }
+}
+
+/// search options shared by every tree searched on behalf of a `Mcts`
+struct MctsConfig<G: Game> {
+    selection_policy: Arc<dyn SelectionPolicy>,
+    rollout_policy: Arc<dyn RolloutPolicy<G>>,
+    virtual_loss: f32,
+    information_set: bool,
+    chance_mode: ChanceMode,
+    backup: Backup,
+    /// capacity of the transposition table, none when transpositions are not merged
+    transpositions: Option<usize>,
+    /// rave equivalence parameter, none when amaf statistics are not collected
+    rave: Option<f32>,
+    /// rollout depth after which the evaluator scores the game, none plays rollouts out
+    evaluation: Option<(usize, Arc<dyn Evaluator<G>>)>,
+    /// priors and leaf values replacing uniform priors and rollouts
+    policy_value: Option<Arc<dyn PolicyValue<G>>>,
+    root_noise: Option<DirichletNoise>,
+    /// seed of the search rng, none seeds from entropy
+    seed: Option<u64>,
+    final_move_selection: FinalMoveSelection,
+    /// failed simulations tolerated per search when failures are retried
+    max_retries: usize,
+    tree_budget: Option<(TreeBudget, BudgetAction)>,
+    progressive_widening: Option<ProgressiveWidening>,
+    /// widening of the outcomes of sampled chance nodes
+    outcome_widening: Option<ProgressiveWidening>,
+    /// heuristic ordering moves for progressive widening, priors are used without one
+    move_ordering: Option<Arc<MoveOrdering<G>>>,
+    solver: bool,
+    normalize_values: bool,
+}
+
+type MoveOrdering<G> = dyn Fn(&G, &<G as Game>::Move) -> f32 + Send + Sync;
+
+impl<G: Game> Clone for MctsConfig<G> {
+    fn clone(&self) -> Self {
+        Self {
+            selection_policy: self.selection_policy.clone(),
+            rollout_policy: self.rollout_policy.clone(),
+            evaluation: self.evaluation.clone(),
+            policy_value: self.policy_value.clone(),
+            move_ordering: self.move_ordering.clone(),
+            ..*self
+        }
+    }
+}
+
+impl<G: Game> Default for MctsConfig<G> {
+    fn default() -> Self {
+        Self {
+            selection_policy: Arc::new(Ucb1::default()),
+            rollout_policy: Arc::new(Uniform),
+            virtual_loss: -1f32,
+            information_set: false,
+            chance_mode: ChanceMode::Sample,
+            backup: Backup::MaxN,
+            transpositions: None,
+            rave: None,
+            evaluation: None,
+            policy_value: None,
+            root_noise: None,
+            seed: None,
+            final_move_selection: FinalMoveSelection::RobustChild,
+            max_retries: 100,
+            tree_budget: None,
+            progressive_widening: None,
+            outcome_widening: None,
+            move_ordering: None,
+            solver: false,
+            normalize_values: false,
+        }
+    }
+}
+
+pub struct MctsBuilder<G: Game> {
+    player_id: G::Player,
+    config: MctsConfig<G>,
+}
+
+impl<G: Game> MctsBuilder<G> {
+    /// tree policy used during selection, defaults to ucb1 with c = sqrt(2)
+    pub fn selection_policy(mut self, policy: impl SelectionPolicy + 'static) -> Self {
+        self.config.selection_policy = Arc::new(policy);
+        self
+    }
+
+    /// default policy playing out games after expansion, defaults to uniformly random moves
+    pub fn rollout_policy(mut self, policy: impl RolloutPolicy<G> + 'static) -> Self {
+        self.config.rollout_policy = Arc::new(policy);
+        self
+    }
+
+    /// score counted for every simulation still in flight through a node during tree parallel
+    /// search, defaults to -1
+    pub fn virtual_loss(mut self, score: f32) -> Self {
+        self.config.virtual_loss = score;
+        self
+    }
+
+    /// single observer information set search, every iteration searches a game redrawn with
+    /// `Game::determinize` from the point of view of the searching player
+    pub fn information_set(mut self, enabled: bool) -> Self {
+        self.config.information_set = enabled;
+        self
+    }
+
+    /// mcts-solver, terminal states are proven as wins or draws and proofs are propagated up
+    /// the tree. proven wins are always played, proven losses avoided and the search ends once
+    /// the root is proven. proofs do not pass through chance nodes, losses below progressively
+    /// widened nodes are not proven and games with imperfect information are never solved.
+    /// the winner is decided by the terminal score alone, so terminal states reached after
+    /// non-terminal scores along the path are left unproven
+    pub fn solver(mut self, enabled: bool) -> Self {
+        self.config.solver = enabled;
+        self
+    }
+
+    /// maps mean values onto [0, 1] before selection, so exploration constants tuned for rewards
+    /// in [0, 1] keep their meaning. values are scaled by `Game::value_bounds` when the game
+    /// declares them and by the range observed so far otherwise. off by default
+    pub fn normalize_values(mut self, enabled: bool) -> Self {
+        self.config.normalize_values = enabled;
+        self
+    }
+
+    /// selection of outcomes at chance nodes, defaults to sampling
+    pub fn chance_mode(mut self, mode: ChanceMode) -> Self {
+        self.config.chance_mode = mode;
+        self
+    }
+
+    /// how scores of several players are backed up, defaults to max^n
+    pub fn backup(mut self, backup: Backup) -> Self {
+        self.config.backup = backup;
+        self
+    }
+
+    /// merges nodes reaching states with the same `Game::state_hash`, turning the tree into a
+    /// dag. once `capacity` states are tracked the least visited half is forgotten, their nodes
+    /// stay in the tree. with a pruning `tree_budget` the tree is pruned instead until at most
+    /// half the capacity is left, bounding both the table and the tree
+    pub fn transpositions(mut self, capacity: usize) -> Self {
+        self.config.transpositions = Some(capacity);
+        self
+    }
+
+    /// collects all moves as first statistics and blends them into selection. `equivalence` is
+    /// the number of visits at which a child's own mean and its amaf mean are weighted equally
+    pub fn rave(mut self, equivalence: f32) -> Self {
+        self.config.rave = Some(equivalence);
+        self
+    }
+
+    /// truncates rollouts after `max_depth` moves and scores the reached game with `evaluator`.
+    /// a depth of 0 evaluates the expanded leaf without any playout
+    pub fn evaluator(mut self, evaluator: impl Evaluator<G> + 'static, max_depth: usize) -> Self {
+        self.config.evaluation = Some((max_depth, Arc::new(evaluator)));
+        self
+    }
+
+    /// alphazero style search, expanded nodes take their children's priors from `provider` and
+    /// its value replaces the rollout of every leaf. selection switches to puct, which can be tuned by setting
+    /// the selection policy afterwards
+    pub fn policy_value(mut self, provider: impl PolicyValue<G> + 'static) -> Self {
+        self.config.policy_value = Some(Arc::new(provider));
+        self.config.selection_policy = Arc::new(Puct::default());
+        self
+    }
+
+    /// mixes dirichlet noise into the priors of the root children, drawn again for every search
+    pub fn root_noise(mut self, noise: DirichletNoise) -> Self {
+        self.config.root_noise = Some(noise);
+        self
+    }
+
+    /// how the move is picked once the search is done, defaults to the most visited child.
+    /// only single threaded searches extend the search for `MaxRobustChild`
+    pub fn final_move_selection(mut self, selection: FinalMoveSelection) -> Self {
+        self.config.final_move_selection = selection;
+        self
+    }
+
+    /// bounds the size of the tree kept between iterations and moves, unbounded by default.
+    /// tree parallel search does not enforce the budget
+    pub fn tree_budget(mut self, budget: TreeBudget, action: BudgetAction) -> Self {
+        self.config.tree_budget = Some((budget, action));
+        self
+    }
+
+    /// expands children one at a time as the parent collects visits instead of all at once,
+    /// keeping games with many moves tractable. moves are added in order of their priors, from
+    /// the policy value provider or `Game::move_prior`
+    pub fn progressive_widening(mut self, widening: ProgressiveWidening) -> Self {
+        self.config.progressive_widening = Some(widening);
+        self
+    }
+
+    /// orders moves for progressive widening by `heuristic`, highest first, instead of by prior
+    pub fn move_ordering(mut self, heuristic: impl Fn(&G, &G::Move) -> f32 + Send + Sync + 'static) -> Self {
+        self.config.move_ordering = Some(Arc::new(heuristic));
+        self
+    }
+
+    /// limits the sampled outcomes kept below chance nodes, once a node holds its limit the
+    /// search revisits outcomes in proportion to their visits. together with
+    /// `progressive_widening` this is double progressive widening. enumerated chance nodes
+    /// always hold every outcome
+    pub fn outcome_widening(mut self, widening: ProgressiveWidening) -> Self {
+        self.config.outcome_widening = Some(widening);
+        self
+    }
+
+    /// failed simulations tolerated per search when failures are retried, defaults to 100
+    pub fn max_retries(mut self, retries: usize) -> Self {
+        self.config.max_retries = retries;
+        self
+    }
+
+    /// seeds the rng used by the search, equal seeds replay the same search on equal games
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    pub fn build(self) -> Mcts<G> {
+        let rng = self.config.seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+        Mcts {
+            tree: MctsTree::new(),
+            root: NodeId(0),
+            player_id: self.player_id,
+            players: Vec::new(),
+            config: self.config,
+            stop: StopHandle::default(),
+            root_noise: Vec::new(),
+            rng,
+            last_search: SearchCounts::default(),
+            budget_reached: false,
+            path: Path::default(),
+        }
+    }
 }
 
 pub struct Mcts<G: Game> {
     tree: MctsTree<G::Move>,
     player_id: G::Player,
+    /// players of the searched game, set when a search starts
+    players: Vec<G::Player>,
     root: NodeId,
+    config: MctsConfig<G>,
+    stop: StopHandle,
+    /// dirichlet noise of each root child in the current search
+    root_noise: Vec<(NodeId, f32)>,
+    rng: StdRng,
+    last_search: SearchCounts,
+    /// an expansion was skipped because the tree budget was reached
+    budget_reached: bool,
+    path: Path,
 }
 
 impl<G: Game> Mcts<G> {
     pub fn new(player_id: G::Player) -> Self {
-        Self { tree: MctsTree::new(), root: NodeId(0), player_id }
-    }
-
-    fn diff_existing_children(&self, existing: &Vec<NodeId>, truth: &Vec<G::Move>) -> Either<Vec<G::Move>, Vec<NodeId>> {
-        let existing = existing.iter()
-            .filter_map(|id|
-                self.tree.node(*id)
-                    .map(|n| (n.placement_move.clone(), *id))
-            )
-            .collect::<HashMap<_, _>>();
-        let diff = truth.iter()
-            .filter(|m| !existing.contains_key(*m))
-            .cloned()
-            .collect::<Vec<_>>();
-        if diff.len() > 0 {
-            Left(diff)
-        } else {
-            // if no difference, no new nodes should exist
-            let nodes = truth.iter()
-                .map(|m| *existing.get(m).unwrap())
-                .collect();
-            Right(nodes)
+        Self::builder(player_id).build()
+    }
+
+    pub fn builder(player_id: G::Player) -> MctsBuilder<G> {
+        MctsBuilder { player_id, config: MctsConfig::default() }
+    }
+
+    fn player_index(&self, player: &G::Player) -> usize {
+        self.players.iter().position(|p| p == player).unwrap_or(0)
+    }
+
+    /// whether `added` more nodes fit into the tree budget, records when they do not
+    fn has_room(&mut self, added: usize) -> bool {
+        let fits = match self.config.tree_budget {
+            None => true,
+            Some((TreeBudget::Nodes(max), _)) => self.tree.nodes.len() + added <= max,
+            Some((TreeBudget::Bytes(max), _)) => self.tree.memory_usage() + added * MctsTree::<G::Move>::node_bytes() <= max,
+        };
+        self.budget_reached |= !fits;
+        fits
+    }
+
+    /// compacts the tree to half the budget once an expansion was skipped or the transposition
+    /// table filled up, between iterations
+    fn prune(&mut self) {
+        let Some((budget, BudgetAction::Prune)) = self.config.tree_budget else {
+            return;
+        };
+        if !std::mem::take(&mut self.budget_reached) {
+            return;
+        }
+        let target = match budget {
+            TreeBudget::Nodes(max) => max / 2,
+            TreeBudget::Bytes(max) => max / 2 / MctsTree::<G::Move>::node_bytes(),
+        };
+        // every state in the table is a node, so a tree this small frees half the table
+        let target = match self.config.transpositions {
+            Some(capacity) if self.tree.transpositions.len() >= capacity => target.min(capacity / 2),
+            _ => target,
+        };
+        let min_visits = self.tree.prune_threshold(self.root, target);
+        self.tree.compact(self.root, min_visits);
+        self.root = NodeId(0);
+    }
+
+    /// estimated bytes held by the search tree, counted from its length rather than its allocations
+    pub fn memory_usage(&self) -> usize {
+        self.tree.memory_usage()
+    }
+
+    /// plays the move into `id` and appends the node with every player's score to the path
+    fn step(&self, game: &mut G, id: NodeId, path: &mut Path) -> Result<()> {
+        let mover = self.player_index(&game.current_player());
+        let s = game.place_move(self.tree.nodes[*id].placement_move.clone())?;
+        let mut terminal = false;
+        for p in &self.players {
+            let score = game.score_state(s.clone(), p.clone());
+            terminal |= score.is_terminal();
+            path.scores.push(score.score());
         }
+        path.steps.push(Step { id, mover, terminal });
+        Ok(())
     }
 
-    fn select(&mut self, game: &mut G) -> Result<Vec<(NodeId, MoveScore)>> {
-        let mut traversal = vec![(self.root, MoveScore::None)];
-        let mut pending_move_diff: Option<Vec<G::Move>> = None;
+    /// selects and expands into `path`, returning the provider's value of an expanded leaf
+    fn select(&mut self, game: &mut G, path: &mut Path, rng: &mut impl Rng) -> Result<Option<Vec<f32>>> {
+        let bounds = self.selection_bounds(game, self.tree.value_bounds);
+        let root = Step { id: self.root, mover: self.player_index(&self.player_id), terminal: false };
+        path.reset(root, self.players.len());
         loop {
-            let (last_id, _) = *traversal.last().unwrap();
-            let mut node_children = self.tree.children(last_id).unwrap();
-            if node_children.len() == 0 {
-                // println!("selection: {traversal:?}");
+            let last_id = path.last().id;
+
+            if let Some(capacity) = self.config.transpositions {
+                if self.tree.nodes[*last_id].hash.is_none() {
+                    if let Some(hash) = game.state_hash() {
+                        // a full table prunes the tree after this iteration, which also drops
+                        // the states of the pruned nodes
+                        let pruning = matches!(self.config.tree_budget, Some((_, BudgetAction::Prune)));
+                        self.budget_reached |= pruning && self.tree.transpositions.len() >= capacity;
+                        self.tree.register_state(last_id, hash, if pruning { usize::MAX } else { capacity });
+                    }
+                }
+                // a state repeating along the path is treated as a leaf
+                let state = *self.tree.canonical[*last_id];
+                if path.steps[..path.steps.len() - 1].iter().any(|s| *self.tree.canonical[*s.id] == state) {
+                    return Ok(None);
+                }
+            }
+
+            let outcomes = game.chance_outcomes();
+            if !outcomes.is_empty() {
+                let Some((child, expanded)) = self.select_outcome(last_id, &outcomes, rng)? else {
+                    return Ok(None);
+                };
+                self.step(game, child, path)?;
+                if expanded {
+                    return Ok(None);
+                }
+                continue;
+            }
+
+            // children are walked as a borrowed slice and steps are appended to the reused path
+            let children = self.tree.child_range(last_id);
+            if children.is_empty() {
                 break;
             }
+            // nodes below their widening limit expand another move before selecting among children
+            let widening = self.config.progressive_widening
+                .is_some_and(|w| children.len() < w.limit(self.tree.stats[*last_id].visits));
 
-            // scan current moves, checking if does not match all moves in existing children nodes
-            if !G::IS_PERFECT_INFORMATION {
-                match self.diff_existing_children(&node_children, &game.possible_moves()) {
-                    Left(diff) => {
-                        pending_move_diff.replace(diff);
-                        break;
-                    },
-                    Right(valid_nodes) => {
-                        // filter node_children, limit to possible moves only
-                        node_children = valid_nodes;
+            // only children reachable in this game are considered, children are available when
+            // their move is legal here and their statistics are compared against that count
+            let legal = (!G::IS_PERFECT_INFORMATION).then(|| game.possible_moves());
+            if let Some(legal) = &legal {
+                let mut reachable = false;
+                for edge in children.clone() {
+                    let n = &mut self.tree.nodes[*self.tree.edges[edge]];
+                    if legal.contains(&n.placement_move) {
+                        n.availability += 1;
+                        reachable = true;
                     }
                 }
+                let untried = legal.iter()
+                    .any(|m| !self.tree.child_ids(last_id).iter().any(|id| self.tree.nodes[**id].placement_move == *m));
+                let widened = self.config.progressive_widening.is_none() || widening;
+                if !reachable || (untried && widened) {
+                    break;
+                }
+            } else if widening && game.possible_moves().len() > children.len() {
+                break;
             }
 
-            let stats = node_children.iter().filter_map(|n| self.tree.node(*n));
-            // let total_visits = stats.clone().fold(0, |acc, s| acc + s.visits);
-            let total_visits = self.tree.node(last_id).unwrap().visits;
-            let (selected_node, _best_uct, placement_move) = stats.enumerate()
-                    .map(|(i, s)| (i, uct(s.score, s.visits, total_visits, 2f32.sqrt()), s.placement_move.clone()))
-                    .max_by(|(_, a, _), (_, b, _)| a.total_cmp(b))
-                    .unwrap();
-            
-            let s = game.place_move(placement_move)?;
-            let last_score = game.score_state(s, self.player_id.clone());
-            traversal.push((node_children[selected_node], last_score));
+            let transposed = self.config.transpositions.is_some();
+            let total_visits = if transposed {
+                self.tree.nodes[*self.tree.canonical[*last_id]].state.visits
+            } else {
+                self.tree.stats[*last_id].visits
+            };
+            let noise = self.config.root_noise.filter(|_| last_id == self.root);
+            if let Some(noise) = noise {
+                // drawn again once the root children change
+                let ids = &self.tree.edges[children.clone()];
+                if !self.root_noise.iter().map(|(id, _)| id).eq(ids.iter()) {
+                    let sampled = noise.sample(ids.len(), rng);
+                    self.root_noise = ids.iter().copied().zip(sampled).collect();
+                }
+            }
+            // proven wins are always picked and proven losses never, proven draws are scored at
+            // their draw value without exploration but only take every other visit, the rest go to
+            // the best unproven child so the parent can still be proven
+            let mover = self.solving().then(|| self.player_index(&game.current_player()));
+            let tree = &self.tree;
+            let selected = tree.edges[children].iter()
+                .enumerate()
+                .filter(|(_, id)| legal.as_ref().is_none_or(|l| l.contains(&tree.nodes[***id].placement_move)))
+                .map(|(i, id)| {
+                    let s = &tree.nodes[**id];
+                    let mut stats = if transposed { tree.transposed_stats(*id) } else { tree.stats[**id] };
+                    if let Some(equivalence) = self.config.rave {
+                        stats = s.rave_stats(stats, equivalence);
+                    }
+                    if let Some(bounds) = bounds {
+                        stats = bounds.normalize(stats);
+                    }
+                    if let Some(noise) = noise {
+                        stats.prior = noise.mix(stats.prior, self.root_noise[i].1);
+                    }
+                    let parent_visits = if G::IS_PERFECT_INFORMATION { total_visits } else { s.availability };
+                    let proof = mover.and(tree.nodes[*tree.canonical[**id]].proof);
+                    let value = match (mover, proof) {
+                        (Some(mover), Some(Proof::Win(winner))) if winner == mover => f32::INFINITY,
+                        (_, Some(Proof::Win(_))) => f32::NEG_INFINITY,
+                        (_, Some(Proof::Draw)) => stats.mean(),
+                        _ => self.config.selection_policy.value(&stats, parent_visits, rng),
+                    };
+                    (*id, value, proof)
+                })
+                .fold((None, None), |(best, open), child| {
+                    let better = |b: Option<(NodeId, f32, Option<Proof>)>| b.is_none_or(|b| child.1.total_cmp(&b.1).is_ge());
+                    let open = if child.2.is_none() && better(open) { Some(child) } else { open };
+                    (if better(best) { Some(child) } else { best }, open)
+                });
+            let selected = match selected {
+                (Some((_, _, Some(Proof::Draw))), Some(open)) if total_visits % 2 == 1 => open.0,
+                (Some(best), _) => best.0,
+                (None, _) => break,
+            };
+
+            self.step(game, selected, path)?;
 
         }
 
         
         // expansion step
-        let (selected_node, last_score) = *traversal.last().unwrap();
+        let selected_node = path.last().id;
 
         // exit early if terminal node was selected
-        if last_score.is_terminal() {
-            return Ok(traversal);
+        if path.last().terminal {
+            return Ok(None);
         }
 
-        let next_selection = if let Some(mut pending_move_diff) = pending_move_diff {
-            // extension of selection, expand but take into account only new nodes - only randomly select from new nodes
-            *pending_move_diff.iter_mut()
-                .filter_map(|placement_move| self.tree.add_child(selected_node, placement_move.clone()))
-                .collect::<Vec<_>>() // hack
-                .choose(&mut rand::thread_rng())
-                .unwrap()
-        } else {
-            for m in game.possible_moves() {
-                self.tree.add_child(selected_node, m);
-            }
-    
-            *self.tree.children(selected_node).unwrap().choose(&mut rand::thread_rng()).unwrap()
+        // expand every move not in the tree yet, but only randomly select from new nodes
+        let moves = game.possible_moves();
+        let (priors, value) = match self.evaluate_leaf(game, &moves)? {
+            Some((priors, value)) => (priors, Some(value)),
+            None => {
+                let weights = moves.iter().map(|m| game.move_prior(m)).collect::<Vec<_>>();
+                let total = weights.iter().sum::<f32>();
+                let priors = if total > 0f32 {
+                    weights.iter().map(|w| w / total).collect()
+                } else {
+                    vec![1f32 / (moves.len() as f32); moves.len()]
+                };
+                (priors, None)
+            },
         };
+        // moves are widened in order of the heuristic, or of their priors without one
+        let ordering = self.config.move_ordering.as_ref().filter(|_| self.config.progressive_widening.is_some());
+        let existing = self.tree.child_ids(selected_node);
+        let mut untried = moves.into_iter()
+            .zip(priors)
+            .filter(|(m, _)| !existing.iter().any(|id| self.tree.nodes[**id].placement_move == *m))
+            .map(|(m, prior)| {
+                let order = ordering.map_or(prior, |heuristic| heuristic(game, &m));
+                (m, prior, order)
+            })
+            .collect::<Vec<_>>();
+        if let Some(widening) = self.config.progressive_widening {
+            // shuffled first so equally ordered moves are widened in random order
+            untried.shuffle(rng);
+            untried.sort_by(|(_, _, a), (_, _, b)| b.total_cmp(a));
+            let limit = widening.limit(self.tree.stats[*selected_node].visits);
+            untried.truncate(limit.saturating_sub(existing.len()).max(1));
+        }
+        if selected_node != self.root && !self.has_room(untried.len()) {
+            return Ok(None);
+        }
+        let (untried, priors): (Vec<_>, Vec<_>) = untried.into_iter().map(|(m, prior, _)| (m, prior)).unzip();
+        let added = untried.into_iter()
+            .filter_map(|placement_move| self.tree.add_child(selected_node, placement_move))
+            .collect::<Vec<_>>();
+        for (id, prior) in added.iter().zip(priors) {
+            self.tree.stats[**id].prior = prior;
+            self.tree.nodes[**id].availability = 1;
+        }
+        // the provider's value stands in for the rollout of the expanded node
+        if value.is_some() {
+            return Ok(value);
+        }
+        let next_selection = *added.choose(rng).ok_or_else(|| anyhow!("no moves to expand in a non terminal state"))?;
+
+        self.step(game, next_selection, path)?;
+        Ok(None)
+    }
+
+    /// priors of `moves` and the value of the game from the policy value provider, none without
+    /// one. fails when the provider does not score every move and player
+    fn evaluate_leaf(&self, game: &G, moves: &[G::Move]) -> Result<Option<(Vec<f32>, Vec<f32>)>> {
+        let Some(provider) = &self.config.policy_value else {
+            return Ok(None);
+        };
+        let (priors, value) = provider.evaluate(game, moves);
+        if priors.len() != moves.len() {
+            return Err(anyhow!("policy value provider returned {} priors for {} moves", priors.len(), moves.len()));
+        }
+        if value.len() != self.players.len() {
+            return Err(anyhow!("policy value provider returned {} values for {} players", value.len(), self.players.len()));
+        }
+        Ok(Some((priors, value)))
+    }
+
+    /// picks the outcome of a chance node, returns whether the outcome was newly expanded
+    /// none when the drawn outcome is not in the tree and the tree budget leaves no room for it
+    fn select_outcome(&mut self, node: NodeId, outcomes: &[(G::Move, f32)], rng: &mut impl Rng) -> Result<Option<(NodeId, bool)>> {
+        self.tree.nodes[*node].chance = true;
+        let find = |tree: &MctsTree<G::Move>, m: &G::Move| tree.child_ids(node).iter()
+            .copied()
+            .find(|id| tree.nodes[**id].placement_move == *m);
 
-        let s = game.place_move(self.tree.node(next_selection).unwrap().placement_move.clone()).unwrap();
-        let last_score = game.score_state(s, self.player_id.clone());
+        let add_outcome = |tree: &mut MctsTree<G::Move>, (m, p): &(G::Move, f32)| {
+            let id = tree.add_child(node, m.clone())?;
+            tree.stats[*id].prior = *p;
+            Some(id)
+        };
 
-        traversal.push((next_selection, last_score));
-        Ok(traversal)
+        Ok(match self.config.chance_mode {
+            ChanceMode::Sample => {
+                let outcome = sample_outcome(outcomes, rng)?;
+                let widened = self.config.outcome_widening
+                    .is_none_or(|w| self.tree.child_ids(node).len() < w.limit(self.tree.stats[*node].visits));
+                match find(&self.tree, &outcome.0) {
+                    Some(id) => Some((id, false)),
+                    None if !widened => {
+                        // at the widening limit, outcomes in the tree are revisited by their visits
+                        let drawable = outcomes.iter().filter_map(|(m, _)| find(&self.tree, m)).collect::<Vec<_>>();
+                        drawable.choose_weighted(rng, |id| self.tree.stats[**id].visits.max(1))
+                            .ok()
+                            .map(|id| (*id, false))
+                    },
+                    None if self.has_room(1) => add_outcome(&mut self.tree, outcome).map(|id| (id, true)),
+                    None => None,
+                }
+            },
+            ChanceMode::Enumerate => {
+                let mut missing = outcomes.iter()
+                    .filter(|(m, _)| find(&self.tree, m).is_none())
+                    .collect::<Vec<_>>();
+                // without room for every outcome only the expanded ones are picked from
+                if !missing.is_empty() && !self.has_room(missing.len()) {
+                    missing.clear();
+                }
+                let added = missing.into_iter()
+                    .filter_map(|o| Some((add_outcome(&mut self.tree, o)?, o.1)))
+                    .collect::<Vec<_>>();
+                if let Ok((id, _)) = added.choose_weighted(rng, |(_, p)| *p) {
+                    return Ok(Some((*id, true)));
+                }
+                outcomes.iter()
+                    .filter_map(|(m, _)| find(&self.tree, m))
+                    .min_by(|a, b| {
+                        let ratio = |id: &NodeId| self.tree.stats[**id].visits as f32 / self.tree.stats[**id].prior;
+                        ratio(a).total_cmp(&ratio(b))
+                    })
+                    .map(|id| (id, false))
+            },
+        })
     }
 
-    // only returns scoring of terminal state
-    fn rollout(&mut self, game: &mut G) -> Result<f32> {
-        let mut acc_score = 0f32;
+    // only returns scoring of terminal state (or the evaluation of a truncated rollout), moves are added to `played` with their mover
+    fn rollout(&self, game: &mut G, mut played: Option<&mut HashSet<(usize, G::Move)>>, rng: &mut impl Rng) -> Result<Vec<f32>> {
+        let mut acc_score = vec![0f32; self.players.len()];
+        let mut depth = 0;
         loop {
-            let random_move = game.possible_moves().choose(&mut rand::thread_rng()).unwrap().clone();
+            let truncated = self.config.evaluation.as_ref()
+                .filter(|(max_depth, _)| depth >= *max_depth)
+                .map(|(_, evaluator)| evaluator.evaluate(game));
+            if let Some(scores) = truncated.or_else(|| self.config.rollout_policy.cutoff(game, depth)) {
+                for (acc, s) in acc_score.iter_mut().zip(scores) {
+                    *acc += s;
+                }
+                return Ok(acc_score);
+            }
+            let outcomes = game.chance_outcomes();
+            let random_move = if outcomes.is_empty() {
+                let moves = game.possible_moves();
+                if moves.is_empty() {
+                    return Err(anyhow!("no legal moves in a non terminal state"));
+                }
+                let m = moves[self.config.rollout_policy.choose(game, &moves, rng)].clone();
+                if let Some(played) = played.as_mut() {
+                    played.insert((self.player_index(&game.current_player()), m.clone()));
+                }
+                m
+            } else {
+                sample_outcome(&outcomes, rng)?.0.clone()
+            };
             let s = game.place_move(random_move)?;
-            let score = game.score_state(s, self.player_id.clone());
-            acc_score += score.score();
-            if score.is_terminal() {
+            let mut terminal = false;
+            for (acc, p) in acc_score.iter_mut().zip(self.players.iter()) {
+                let score = game.score_state(s.clone(), p.clone());
+                *acc += score.score();
+                terminal |= score.is_terminal();
+            }
+            if terminal {
                 return Ok(acc_score);
             }
+            depth += 1;
+        }
+    }
+
+    /// value of accumulated scores for the node `mover` moved into
+    fn node_value(&self, acc_score: &[f32], mover: usize) -> f32 {
+        let searcher = self.player_index(&self.player_id);
+        match self.config.backup {
+            Backup::MaxN => acc_score[mover],
+            Backup::Paranoid if mover == searcher => acc_score[searcher],
+            Backup::Paranoid => -acc_score[searcher],
+            Backup::Negamax => {
+                let others = acc_score.iter().sum::<f32>() - acc_score[mover];
+                acc_score[mover] - others / ((acc_score.len() - 1).max(1) as f32)
+            },
+        }
+    }
+
+    /// bounds mean values are normalised by during selection, none leaves them as they are.
+    /// `observed` is used when the game does not declare bounds
+    fn selection_bounds(&self, game: &G, observed: Option<ValueBounds>) -> Option<ValueBounds> {
+        if !self.config.normalize_values {
+            return None;
+        }
+        let Some(ValueBounds { min, max }) = game.value_bounds() else {
+            return observed;
+        };
+        // declared bounds are of accumulated scores, mapped into the values of the backup
+        Some(match self.config.backup {
+            Backup::MaxN => ValueBounds::new(min, max),
+            Backup::Paranoid => ValueBounds::new(min.min(-max), max.max(-min)),
+            Backup::Negamax => ValueBounds::new(min - max, max - min),
+        })
+    }
+
+    fn solving(&self) -> bool {
+        self.config.solver && G::IS_PERFECT_INFORMATION
+    }
+
+    /// whether the solver has proven the value of the root
+    fn root_proven(&self) -> bool {
+        self.tree.nodes[*self.tree.canonical[*self.root]].proof.is_some()
+    }
+
+    /// proves a terminal leaf and passes proofs up the path as far as they decide the parent
+    fn prove(&mut self, path: &Path) {
+        let (traversal, leaf) = (&path.steps, path.last());
+        let leaf_state = *self.tree.canonical[*leaf.id];
+        // values also count the non-terminal scores along the path, so a leaf reached through any
+        // is not proven by its terminal score alone
+        let unscored = (0..traversal.len() - 1).all(|i| path.scores(i).iter().all(|s| *s == 0f32));
+        if leaf.terminal && unscored && self.tree.nodes[leaf_state].proof.is_none() {
+            // the player with the strictly highest terminal score wins
+            let scores = path.scores(traversal.len() - 1);
+            let best = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let mut winners = scores.iter().enumerate().filter(|(_, v)| **v == best);
+            self.tree.nodes[leaf_state].proof = match (winners.next(), winners.next()) {
+                (Some((winner, _)), None) => Some(Proof::Win(winner)),
+                _ => Some(Proof::Draw),
+            };
+        }
+
+        for i in (1..traversal.len()).rev() {
+            let Some(proof) = self.tree.nodes[*self.tree.canonical[*traversal[i].id]].proof else {
+                break;
+            };
+            let parent = traversal[i - 1].id;
+            let parent_state = *self.tree.canonical[*parent];
+            if self.tree.nodes[*parent].chance {
+                break;
+            }
+            // a transposition may have been proven through another path, its ancestors on this
+            // one still have to be updated
+            if self.tree.nodes[parent_state].proof.is_some() {
+                continue;
+            }
+            // every child of the parent is moved into by the same player
+            let mover = traversal[i].mover;
+            let parent_proof = if proof == Proof::Win(mover) {
+                Some(proof)
+            } else if self.config.progressive_widening.is_some() {
+                None
+            } else {
+                // the mover picks the best of its children once all of them are proven
+                let proofs = self.tree.child_ids(parent).iter()
+                    .map(|id| self.tree.nodes[*self.tree.canonical[**id]].proof)
+                    .collect::<Option<Vec<_>>>();
+                proofs.and_then(|proofs| {
+                    if proofs.contains(&Proof::Win(mover)) {
+                        Some(Proof::Win(mover))
+                    } else if proofs.contains(&Proof::Draw) {
+                        Some(Proof::Draw)
+                    } else {
+                        // every child is won by another player, the parent is only decided if
+                        // they are all won by the same one
+                        proofs.iter().all(|p| *p == proofs[0]).then_some(proofs[0])
+                    }
+                })
+            };
+            let Some(parent_proof) = parent_proof else {
+                break;
+            };
+            self.tree.nodes[parent_state].proof = Some(parent_proof);
         }
     }
 
-    fn backpropagate(&mut self, traversal: &Vec<(NodeId, MoveScore)>, rollout_score: f32) {
+    /// `played` holds the moves of the rollout, used for amaf statistics
+    fn backpropagate(&mut self, path: &Path, rollout_score: Vec<f32>, mut played: HashSet<(usize, G::Move)>) {
+        let traversal = &path.steps;
         let mut acc_score = rollout_score;
-        for (id, move_score) in traversal.iter().rev() {
-            acc_score += move_score.score();
-            let n = &mut self.tree.nodes[**id];
-            n.visits += 1;
-            n.score += acc_score;
+        for (i, step) in traversal.iter().enumerate().rev() {
+            for (acc, s) in acc_score.iter_mut().zip(path.scores(i)) {
+                *acc += s;
+            }
+            let value = self.node_value(&acc_score, step.mover);
+            self.tree.observe_value(value);
+            self.tree.nodes[*step.id].player = step.mover;
+            let stats = &mut self.tree.stats[*step.id];
+            stats.visits += 1;
+            stats.score += value;
+            stats.score_sq += value * value;
+            if self.config.transpositions.is_some() {
+                let state = &mut self.tree.nodes[*self.tree.canonical[*step.id]].state;
+                state.visits += 1;
+                state.score += value;
+                state.score_sq += value * value;
+            }
+
+            // siblings whose move the mover played at any later point share the result
+            let Some(parent) = i.checked_sub(1).map(|p| traversal[p].id) else {
+                continue;
+            };
+            if self.config.rave.is_none() || self.tree.nodes[*parent].chance {
+                continue;
+            }
+            played.insert((step.mover, self.tree.nodes[*step.id].placement_move.clone()));
+            for edge in self.tree.child_range(parent) {
+                let n = &mut self.tree.nodes[*self.tree.edges[edge]];
+                if played.contains(&(step.mover, n.placement_move.clone())) {
+                    n.amaf_visits += 1;
+                    n.amaf_score += value;
+                }
+            }
+        }
+    }
+
+    /// root children the move can be picked from
+    fn root_candidates(&self, base_game: &G) -> Vec<NodeId> {
+        // reused trees can hold root children that are not legal in this game
+        let legal = (!G::IS_PERFECT_INFORMATION).then(|| base_game.possible_moves());
+        let candidates = self.tree.child_ids(self.root).iter()
+            .copied()
+            .filter(|id| legal.as_ref().is_none_or(|l| l.contains(&self.tree.nodes[**id].placement_move)))
+            .collect::<Vec<_>>();
+        if !self.solving() {
+            return candidates;
         }
+        // proven wins are always taken, draws unless an unproven child has a better mean and
+        // proven losses only when nothing else is left
+        let mover = self.player_index(&base_game.current_player());
+        let proof = |id: &NodeId| self.tree.nodes[*self.tree.canonical[**id]].proof;
+        let with_proof = |p: Option<Proof>| candidates.iter().copied().filter(|id| proof(id) == p).collect::<Vec<_>>();
+        let (wins, draws) = (with_proof(Some(Proof::Win(mover))), with_proof(Some(Proof::Draw)));
+        let mean = |id: &NodeId| self.tree.stats[**id].mean();
+        let draw_mean = draws.iter().map(mean).fold(f32::NEG_INFINITY, f32::max);
+        // unvisited children have no mean to compare and are only played without a draw
+        let open = with_proof(None).into_iter()
+            .filter(|id| draws.is_empty() || (self.tree.stats[**id].visits > 0 && mean(id) > draw_mean))
+            .collect::<Vec<_>>();
+        [wins, open, draws].into_iter().find(|c| !c.is_empty()).unwrap_or(candidates)
     }
 
-    // calculate best average score
-    fn best_descendant(&self) -> (&MctsNode<<G as Game>::Move>, f32) {
-        self.tree.children[*self.root].iter()
-            .filter_map(|n| self.tree.node(*n))
-            // .map(|n| (n, n.score / (n.visits as f32)))
-            .map(|n| (n, n.visits as f32))
+    /// child picked by the final move selection, the most visited child when moves are sampled
+    fn best_descendant(&self, base_game: &G) -> Option<NodeId> {
+        let value = |n: &ChildStats| match self.config.final_move_selection {
+            // unvisited children have no mean to compare
+            FinalMoveSelection::MaxChild | FinalMoveSelection::SecureChild { .. } if n.visits == 0 => f32::NEG_INFINITY,
+            FinalMoveSelection::MaxChild => n.mean(),
+            FinalMoveSelection::SecureChild { c } => n.mean() - c / (n.visits as f32).sqrt(),
+            FinalMoveSelection::RobustChild
+            | FinalMoveSelection::MaxRobustChild { .. }
+            | FinalMoveSelection::Temperature(_) => n.visits as f32,
+        };
+        self.root_candidates(base_game).into_iter()
+            .map(|id| (id, value(&self.tree.stats[*id])))
             .max_by(|(_, a), (_, b)| a.total_cmp(b))
-            .unwrap()
+            .map(|(id, _)| id)
     }
 
-    pub fn best_move(&mut self, base_game: &G, iterations: usize, retry_failed: bool) -> G::Move {
-        let mut simulate = || -> Result<()> {
-            let mut game = base_game.clone();
+    /// whether the child with the highest mean is also the most visited
+    fn max_robust_agrees(&self, base_game: &G) -> bool {
+        let candidates = self.root_candidates(base_game).into_iter().map(|id| self.tree.stats[*id]).collect::<Vec<_>>();
+        let max_visits = candidates.iter().map(|n| n.visits).max();
+        let max_child = candidates.iter().filter(|n| n.visits > 0).max_by(|a, b| a.mean().total_cmp(&b.mean()));
+        max_child.is_some_and(|n| Some(n.visits) == max_visits)
+    }
+
+    fn final_move(&mut self, base_game: &G) -> Result<G::Move, MctsError> {
+        let best = |this: &Self| this.best_descendant(base_game)
+            .map(|id| this.tree.nodes[*id].placement_move.clone())
+            .ok_or(MctsError::NoLegalMoves);
+        let FinalMoveSelection::Temperature(temperature) = self.config.final_move_selection else {
+            return best(self);
+        };
+        let candidates = self.root_candidates(base_game).into_iter()
+            .map(|id| (self.tree.nodes[*id].placement_move.clone(), self.tree.stats[*id].visits))
+            .collect::<Vec<_>>();
+        let max_visits = candidates.iter().map(|(_, v)| *v).max().unwrap_or(0).max(1) as f32;
+        // relative to the most visited child so low temperatures do not overflow
+        let weight = |visits: u32| (visits as f32 / max_visits).powf(1f32 / temperature.max(f32::EPSILON));
+        match candidates.choose_weighted(&mut self.rng, |(_, v)| weight(*v)) {
+            Ok((m, _)) => Ok(m.clone()),
+            Err(_) => best(self),
+        }
+    }
+
+    fn search(&mut self, base_game: &G, limit: &SearchLimit, retry_failed: bool) -> Result<SearchCounts, MctsError> {
+        let start = Instant::now();
+        self.players = base_game.players();
+        self.root_noise.clear();
+        let mut rng = self.rng.clone();
+        let max_retries = self.config.max_retries;
+        let mut path = std::mem::take(&mut self.path);
+        // returns whether the root is proven
+        let mut simulate = || -> Result<bool> {
+            self.prune();
+            let mut game = self.iteration_game(base_game, &mut rng);
             // let mut last_score: Option<f32> = None;
             // select and expand
-            let selected = self.select(&mut game)?;
-
-            let (_, last_score) = selected.last().unwrap();
+            let leaf_value = self.select(&mut game, &mut path, &mut rng)?;
 
             // rollout
-            let rollout_score = if !last_score.is_terminal() {
-                self.rollout(&mut game)?
-            } else {
-                0f32
+            let mut played = HashSet::new();
+            let rollout_score = match leaf_value {
+                Some(value) => value,
+                None if path.last().terminal => vec![0f32; self.players.len()],
+                // leaves that were not expanded, e.g. drawn outcomes or leaves beyond the tree
+                // budget, are valued by the provider as well
+                None if self.config.policy_value.is_some() => {
+                    let moves = game.possible_moves();
+                    self.evaluate_leaf(&game, &moves)?.map_or_else(|| vec![0f32; self.players.len()], |(_, value)| value)
+                },
+                None => self.rollout(&mut game, self.config.rave.is_some().then_some(&mut played), &mut rng)?,
             };
             // backprop
-            self.backpropagate(&selected, rollout_score);
+            self.backpropagate(&path, rollout_score, played);
+            if self.solving() {
+                self.prove(&path);
+            }
             // println!("iter {i}: {} nodes", self.tree.len());
-            Ok(())
+            Ok(self.root_proven())
         };
 
+        // always complete one iteration so a move can be picked
         let mut i = 0usize;
-        while i < iterations {
-            if let Err(e) = simulate() {
-                if retry_failed {
-                    eprintln!("encountered error during simulation {e}");
-                    continue;
+        let mut failed = 0usize;
+        let mut error = None;
+        let mut proven = false;
+        // a proven root needs no further search
+        while i == 0 || (limit.proceed(i) && !proven) {
+            match simulate() {
+                Ok(root_proven) => {
+                    i += 1;
+                    proven = root_proven;
+                },
+                Err(e) if !retry_failed => {
+                    error = Some(MctsError::Game(e));
+                    break;
+                },
+                Err(e) => {
+                    // failed simulations do not count as iterations
+                    failed += 1;
+                    if failed > max_retries {
+                        error = Some(MctsError::RetriesExhausted { failed, last: e });
+                        break;
+                    }
+                },
+            }
+        }
+        self.rng = rng;
+        self.path = path;
+        match error {
+            Some(e) => Err(e),
+            None => Ok(SearchCounts { iterations: i, failed, elapsed: start.elapsed() }),
+        }
+    }
+
+    fn iteration_game(&self, base_game: &G, rng: &mut impl Rng) -> G {
+        let mut game = if self.config.information_set {
+            base_game.determinize(self.player_id.clone(), rng)
+        } else {
+            base_game.clone()
+        };
+        game.reseed(rng);
+        game
+    }
+
+    fn search_best_move(&mut self, base_game: &G, limit: SearchLimit, retry_failed: bool) -> Result<G::Move, MctsError> {
+        if base_game.possible_moves().is_empty() {
+            return Err(MctsError::NoLegalMoves);
+        }
+        let searched = self.search(base_game, &limit, retry_failed)
+            .and_then(|counts| {
+                self.last_search = counts;
+                self.extend_max_robust(base_game, &limit, retry_failed)
+            });
+        self.stop.finish();
+        searched?;
+        self.final_move(base_game)
+    }
+
+    /// keeps searching in batches until the max and robust child agree
+    fn extend_max_robust(&mut self, base_game: &G, limit: &SearchLimit, retry_failed: bool) -> Result<(), MctsError> {
+        let FinalMoveSelection::MaxRobustChild { extra_iterations } = self.config.final_move_selection else {
+            return Ok(());
+        };
+        let mut remaining = extra_iterations;
+        while remaining > 0 && limit.proceed(0) && !self.root_proven() && !self.max_robust_agrees(base_game) {
+            let batch = remaining.min(100);
+            let extra = SearchLimit { iterations: batch, ..limit.clone() };
+            let counts = self.search(base_game, &extra, retry_failed)?;
+            self.last_search.iterations += counts.iterations;
+            self.last_search.failed += counts.failed;
+            self.last_search.elapsed += counts.elapsed;
+            remaining -= batch;
+        }
+        Ok(())
+    }
+
+    /// searches for `iterations` iterations, panics if the search fails (see `try_best_move`)
+    pub fn best_move(&mut self, base_game: &G, iterations: usize, retry_failed: bool) -> G::Move {
+        self.try_best_move(base_game, iterations, retry_failed).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// searches for `iterations` iterations. failed simulations are retried up to the retry
+    /// budget when `retry_failed` is set, otherwise the first failure ends the search
+    pub fn try_best_move(&mut self, base_game: &G, iterations: usize, retry_failed: bool) -> Result<G::Move, MctsError> {
+        let limit = SearchLimit { iterations, deadline: None, stop: self.stop.clone() };
+        self.search_best_move(base_game, limit, retry_failed)
+    }
+
+    /// searches for the given wall-clock budget, panics if the search fails
+    pub fn best_move_for(&mut self, base_game: &G, budget: Duration, retry_failed: bool) -> G::Move {
+        self.try_best_move_for(base_game, budget, retry_failed).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_best_move_for(&mut self, base_game: &G, budget: Duration, retry_failed: bool) -> Result<G::Move, MctsError> {
+        self.try_best_move_until(base_game, Instant::now() + budget, retry_failed)
+    }
+
+    /// searches until the deadline has passed, panics if the search fails
+    pub fn best_move_until(&mut self, base_game: &G, deadline: Instant, retry_failed: bool) -> G::Move {
+        self.try_best_move_until(base_game, deadline, retry_failed).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_best_move_until(&mut self, base_game: &G, deadline: Instant, retry_failed: bool) -> Result<G::Move, MctsError> {
+        let limit = SearchLimit { iterations: usize::MAX, deadline: Some(deadline), stop: self.stop.clone() };
+        self.search_best_move(base_game, limit, retry_failed)
+    }
+
+    /// root parallel search, panics if the search fails (see `try_best_move_parallel`)
+    pub fn best_move_parallel(&mut self, base_game: &G, iterations: usize, threads: usize, retry_failed: bool) -> G::Move
+    where
+        G: Send,
+        G::Move: Send,
+        G::Player: Send,
+    {
+        self.try_best_move_parallel(base_game, iterations, threads, retry_failed).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// root parallel search, every thread grows an independent tree from the same root and the
+    /// statistics of the root children are merged into this tree before picking a move. the
+    /// first failed search of any thread is returned
+    pub fn try_best_move_parallel(&mut self, base_game: &G, iterations: usize, threads: usize, retry_failed: bool) -> Result<G::Move, MctsError>
+    where
+        G: Send,
+        G::Move: Send,
+        G::Player: Send,
+    {
+        if base_game.possible_moves().is_empty() {
+            return Err(MctsError::NoLegalMoves);
+        }
+        let threads = threads.max(1);
+        let limit = |iterations| SearchLimit { iterations, deadline: None, stop: self.stop.clone() };
+        let worker_limit = limit(iterations / threads);
+        let own_limit = limit(iterations / threads + iterations % threads);
+        let start = Instant::now();
+
+        let workers = (1..threads).map(|_| self.worker()).collect::<Vec<_>>();
+        let (trees, counts) = thread::scope(|scope| {
+            let workers = workers.into_iter()
+                .map(|mut worker| {
+                    let game = base_game.clone();
+                    let limit = worker_limit.clone();
+                    scope.spawn(move || {
+                        let counts = worker.search(&game, &limit, retry_failed);
+                        (worker.tree, counts)
+                    })
+                })
+                .collect::<Vec<_>>();
+            let counts = self.search(base_game, &own_limit, retry_failed);
+            let trees = workers.into_iter()
+                .map(|w| w.join().expect("search thread panicked"))
+                .collect::<Vec<_>>();
+            (trees, counts)
+        });
+        self.stop.finish();
+
+        let mut counts = counts?;
+        for (tree, worker_counts) in trees {
+            let worker_counts = worker_counts?;
+            self.merge_root_children(&tree);
+            counts.iterations += worker_counts.iterations;
+            counts.failed += worker_counts.failed;
+        }
+        counts.elapsed = start.elapsed();
+        self.last_search = counts;
+        self.final_move(base_game)
+    }
+
+    /// search with the same configuration and an empty tree, stopped by the same handle. its rng
+    /// is seeded from this search's rng
+    fn worker(&mut self) -> Self {
+        Self {
+            tree: MctsTree::new(),
+            root: NodeId(0),
+            player_id: self.player_id.clone(),
+            players: self.players.clone(),
+            config: self.config.clone(),
+            stop: self.stop.clone(),
+            root_noise: Vec::new(),
+            rng: StdRng::seed_from_u64(self.rng.gen()),
+            last_search: SearchCounts::default(),
+            budget_reached: false,
+            path: Path::default(),
+        }
+    }
+
+    fn merge_root_children(&mut self, other: &MctsTree<G::Move>) {
+        self.tree.value_bounds = match (self.tree.value_bounds, other.value_bounds) {
+            (Some(a), Some(b)) => Some(a.union(b)),
+            (a, b) => a.or(b),
+        };
+        self.tree.stats[*self.root].visits += other.stats[0].visits;
+        for other_id in other.child_ids(NodeId(0)) {
+            let other_child = &other.nodes[**other_id];
+            let existing = self.tree.child_ids(self.root).iter()
+                .copied()
+                .find(|id| self.tree.nodes[**id].placement_move == other_child.placement_move);
+            let Some(id) = existing.or_else(|| self.tree.add_child(self.root, other_child.placement_move.clone())) else {
+                continue;
+            };
+            let n = &mut self.tree.nodes[*id];
+            n.amaf_score += other_child.amaf_score;
+            n.amaf_visits += other_child.amaf_visits;
+            n.proof = n.proof.or(other_child.proof);
+            let (stats, other_stats) = (&mut self.tree.stats[*id], other.stats[**other_id]);
+            stats.score += other_stats.score;
+            stats.score_sq += other_stats.score_sq;
+            stats.visits += other_stats.visits;
+            stats.prior = other_stats.prior;
+        }
+    }
+
+    /// handle that ends a running search early, the search then returns its current best move.
+    /// a stop requested while no search is running ends the next search after its first iteration
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// moves the root to the child reached by `played`, which can be a move from any player.
+    /// statistics below that child are kept for the next search, returns false if the child was
+    /// never expanded and the tree had to be reset
+    pub fn advance(&mut self, played: &G::Move) -> bool {
+        let child = self.tree.child_ids(self.root).iter()
+            .copied()
+            .find(|id| self.tree.nodes[**id].placement_move == *played);
+        if let Some(child) = child {
+            self.tree.reroot(child);
+            true
+        } else {
+            self.tree = MctsTree::new();
+            false
+        }
+    }
+
+    /// nodes along the most visited children from the root, excluding the root
+    fn principal_variation(&self) -> Vec<NodeId> {
+        // transposed nodes can lead back to an ancestor, every state is only walked once
+        let mut seen = HashSet::new();
+        let mut principal_variation = Vec::new();
+        let mut node = self.root;
+        while seen.insert(*self.tree.canonical[*node]) {
+            let Some(best) = self.tree.child_ids(node).iter().copied().max_by_key(|id| self.tree.stats[**id].visits) else {
+                break;
+            };
+            principal_variation.push(best);
+            node = best;
+        }
+        principal_variation
+    }
+
+    /// report of the last search from the current root, none if the root has no children.
+    /// the principal variation and depth only cover the reachable part of the tree
+    pub fn report(&self, base_game: &G) -> Option<SearchReport<G::Move>> {
+        if self.tree.child_ids(self.root).is_empty() {
+            return None;
+        }
+        let rng = &mut StdRng::seed_from_u64(0);
+        let bounds = self.selection_bounds(base_game, self.tree.value_bounds);
+        let parent_visits = self.tree.stats[*self.root].visits;
+        let children = self.tree.child_ids(self.root).iter()
+            .map(|id| {
+                let stats = self.tree.stats[**id];
+                RootChildReport {
+                    placement_move: self.tree.nodes[**id].placement_move.clone(),
+                    visits: stats.visits,
+                    mean: stats.mean(),
+                    uct: self.config.selection_policy.value(&bounds.map_or(stats, |b| b.normalize(stats)), parent_visits, rng),
+                    proof: self.tree.nodes[*self.tree.canonical[**id]].proof,
                 }
-                panic!("{e}");
+            })
+            .collect();
+
+        let principal_variation = self.principal_variation().iter()
+            .map(|id| self.tree.nodes[**id].placement_move.clone())
+            .collect();
+
+        let mut seen = HashSet::new();
+        let mut max_depth = 0;
+        let mut queue = VecDeque::from([(self.root, 0)]);
+        while let Some((node, depth)) = queue.pop_front() {
+            if !seen.insert(*self.tree.canonical[*node]) {
+                continue;
             }
-            i += 1;
+            max_depth = max_depth.max(depth);
+            queue.extend(self.tree.child_ids(node).iter().map(|id| (*id, depth + 1)));
         }
 
-        let (best_move, best_score) = self.best_descendant();
-        // println!("player {:?}: move {:?} ({best_score})", self.player_id, best_move.placement_move);
-        best_move.placement_move.clone()
+        Some(SearchReport {
+            best_move: self.tree.nodes[*self.best_descendant(base_game)?].placement_move.clone(),
+            children,
+            principal_variation,
+            proof: self.tree.nodes[*self.tree.canonical[*self.root]].proof,
+            nodes: self.tree.nodes.len(),
+            memory_usage: self.tree.memory_usage(),
+            max_depth,
+            iterations: self.last_search.iterations,
+            failed_simulations: self.last_search.failed,
+            elapsed: self.last_search.elapsed,
+        })
     }
 
-    pub fn dump_tree(&self) {
-        self.tree.dump();
+    /// writes the tree with default export options to ./out.dot
+    pub fn dump_tree(&self) -> io::Result<()> {
+        self.export_dot(File::create("./out.dot")?, &DotOptions::default())
     }
 }
@@ -0,0 +1,162 @@
+use hashbrown::HashSet;
+use serde::{de::{DeserializeOwned, Error}, Deserialize, Deserializer, Serialize, Serializer};
+use std::{collections::VecDeque, fmt::Debug, io::{self, Read, Write}};
+
+use super::{Game, Mcts, MctsTree, NodeId};
+
+/// what part of the tree `Mcts::export_dot` writes, defaults keep large trees readable
+#[derive(Debug, Clone, Copy)]
+pub struct DotOptions {
+    /// deepest level written below the root, none writes every level
+    pub max_depth: Option<usize>,
+    /// nodes with fewer visits are left out along with their subtree
+    pub min_visits: u32,
+    /// only the most visited children of every node are written, none writes all of them
+    pub top_k: Option<usize>,
+    /// fills nodes from red to green by their mean value
+    pub colour_by_value: bool,
+    /// draws the principal variation in bold
+    pub highlight_principal_variation: bool,
+}
+
+impl Default for DotOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: Some(6),
+            min_visits: 1,
+            top_k: Some(8),
+            colour_by_value: true,
+            highlight_principal_variation: true,
+        }
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl<Move: Default + Debug> MctsTree<Move> {
+    /// checks that every id of a loaded tree points into it
+    fn validate(&self) -> Result<(), String> {
+        let len = self.nodes.len();
+        if len == 0 || self.stats.len() != len || self.children.len() != len || self.canonical.len() != len {
+            return Err(format!(
+                "inconsistent tree: {len} nodes, {} statistics, {} children lists, {} canonical ids",
+                self.stats.len(), self.children.len(), self.canonical.len(),
+            ));
+        }
+        if let Some(list) = self.children.iter().find(|c| c.range().end > self.edges.len()) {
+            return Err(format!("children list {list:?} outside of {} edges", self.edges.len()));
+        }
+        if let Some(id) = self.children.iter().flat_map(|c| &self.edges[c.range()]).find(|id| ***id >= len || ***id == 0) {
+            return Err(format!("invalid child id {}", **id));
+        }
+        if let Some(id) = self.canonical.iter().find(|id| ***id >= len || *self.canonical[***id] != ***id) {
+            return Err(format!("invalid canonical id {}", **id));
+        }
+        Ok(())
+    }
+}
+
+impl<G: Game> Mcts<G> {
+    /// writes the tree below the current root with any serde format, e.g. a compact binary one
+    pub fn serialize_tree<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        G::Move: Serialize,
+    {
+        self.tree.serialize(serializer)
+    }
+
+    /// replaces the tree with one written by `serialize_tree`, e.g. to continue a checkpointed
+    /// search or to warm start from an opening tree. the tree has to be searched from the same
+    /// position and players it was built for
+    pub fn deserialize_tree<'de, D: Deserializer<'de>>(&mut self, deserializer: D) -> Result<(), D::Error>
+    where
+        G::Move: Deserialize<'de>,
+    {
+        let mut tree = MctsTree::deserialize(deserializer)?;
+        tree.validate().map_err(D::Error::custom)?;
+        tree.index_transpositions();
+        self.tree = tree;
+        self.root = NodeId(0);
+        Ok(())
+    }
+
+    pub fn export_json(&self, writer: impl Write) -> serde_json::Result<()>
+    where
+        G::Move: Serialize,
+    {
+        self.serialize_tree(&mut serde_json::Serializer::new(writer))
+    }
+
+    pub fn import_json(&mut self, reader: impl Read) -> serde_json::Result<()>
+    where
+        G::Move: DeserializeOwned,
+    {
+        self.deserialize_tree(&mut serde_json::Deserializer::from_reader(reader))
+    }
+
+    /// writes the tree below the current root as a graphviz digraph
+    pub fn export_dot(&self, mut writer: impl Write, options: &DotOptions) -> io::Result<()> {
+        // nodes are picked first so colours can be scaled to the written values
+        let mut edges = Vec::new();
+        let mut written = HashSet::new();
+        written.insert(*self.root);
+        let mut queue = VecDeque::from([(self.root, 0)]);
+        while let Some((parent, depth)) = queue.pop_front() {
+            if options.max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+            let mut children = self.tree.child_ids(parent).iter()
+                .copied()
+                .filter(|id| self.tree.stats[**id].visits >= options.min_visits)
+                .collect::<Vec<_>>();
+            children.sort_by_key(|id| std::cmp::Reverse(self.tree.stats[**id].visits));
+            children.truncate(options.top_k.unwrap_or(usize::MAX));
+            for child in children {
+                edges.push((parent, child));
+                // transposed nodes share children, which are only written once
+                if written.insert(*child) {
+                    queue.push_back((child, depth + 1));
+                }
+            }
+        }
+
+        let principal_variation = if options.highlight_principal_variation {
+            self.principal_variation().iter().map(|id| **id).collect()
+        } else {
+            HashSet::new()
+        };
+        let means = written.iter().map(|id| (*id, self.tree.stats[*id].mean())).collect::<Vec<_>>();
+        let (low, high) = means.iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), (_, m)| (low.min(*m), high.max(*m)));
+
+        writeln!(writer, "digraph G {{")?;
+        writeln!(writer, "overlap=\"scalexy\";")?;
+        writeln!(writer, "node [shape=box];")?;
+        let mut nodes = means;
+        nodes.sort_by_key(|(id, _)| *id);
+        for (id, mean) in nodes {
+            let label = format!("{}\\nvisits={}\\nmean={mean:.3}", escape(&format!("{:?}", self.tree.nodes[id].placement_move)), self.tree.stats[id].visits);
+            write!(writer, "{id} [label=\"{label}\"")?;
+            if options.colour_by_value {
+                let scaled = if high > low { (mean - low) / (high - low) } else { 0.5 };
+                // hue 0 is red, 1/3 green
+                write!(writer, " style=filled fillcolor=\"{:.3} 0.4 1.0\"", scaled / 3f32)?;
+            }
+            if principal_variation.contains(&id) {
+                write!(writer, " penwidth=3")?;
+            }
+            writeln!(writer, "];")?;
+        }
+        for (parent, child) in edges {
+            write!(writer, "{} -> {}", *parent, *child)?;
+            if principal_variation.contains(&*child) && (parent == self.root || principal_variation.contains(&*parent)) {
+                write!(writer, " [penwidth=3]")?;
+            }
+            writeln!(writer, ";")?;
+        }
+        writeln!(writer, "}}")?;
+        writer.flush()
+    }
+}
@@ -0,0 +1,61 @@
+use rand::{Rng, RngCore};
+
+use super::{selection::standard_normal, Game};
+
+/// external provider of move priors and state values, e.g. a handwritten heuristic or a model
+pub trait PolicyValue<G: Game>: Send + Sync {
+    /// prior of every move in `moves` and the estimated scores of the players, ordered as in
+    /// `Game::players`
+    fn evaluate(&self, game: &G, moves: &[G::Move]) -> (Vec<f32>, Vec<f32>);
+}
+
+/// dirichlet noise mixed into the priors of the root children, a share of `epsilon` of each
+/// prior is replaced by noise drawn with concentration `alpha`
+#[derive(Debug, Clone, Copy)]
+pub struct DirichletNoise {
+    pub alpha: f32,
+    pub epsilon: f32,
+}
+
+impl Default for DirichletNoise {
+    fn default() -> Self {
+        Self { alpha: 0.3, epsilon: 0.25 }
+    }
+}
+
+impl DirichletNoise {
+    pub(super) fn sample(&self, n: usize, rng: &mut dyn RngCore) -> Vec<f32> {
+        let draws = (0..n).map(|_| gamma(self.alpha, rng)).collect::<Vec<_>>();
+        let total = draws.iter().sum::<f32>();
+        if total > 0f32 {
+            draws.iter().map(|d| d / total).collect()
+        } else {
+            vec![1f32 / n as f32; n]
+        }
+    }
+
+    pub(super) fn mix(&self, prior: f32, noise: f32) -> f32 {
+        (1f32 - self.epsilon) * prior + self.epsilon * noise
+    }
+}
+
+/// gamma(alpha, 1) sample (marsaglia and tsang)
+fn gamma(alpha: f32, rng: &mut dyn RngCore) -> f32 {
+    if alpha < 1f32 {
+        // boosted to alpha + 1 and scaled back down
+        return gamma(alpha + 1f32, rng) * (1f32 - rng.gen::<f32>()).powf(1f32 / alpha);
+    }
+    let d = alpha - 1f32 / 3f32;
+    let c = 1f32 / (9f32 * d).sqrt();
+    loop {
+        let x = standard_normal(rng);
+        let v = (1f32 + c * x).powi(3);
+        if v <= 0f32 {
+            continue;
+        }
+        let u = 1f32 - rng.gen::<f32>();
+        if u.ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::{self, Debug};
+use rand::{distributions::{Distribution, WeightedIndex}, Rng, RngCore};
+
+use super::Game;
+
+/// static evaluation of games that have not ended, used where rollouts are cut short
+pub trait Evaluator<G: Game>: Send + Sync {
+    /// estimated scores of the players, ordered as in `Game::players`
+    fn evaluate(&self, game: &G) -> Vec<f32>;
+}
+
+impl<G: Game, F: Fn(&G) -> Vec<f32> + Send + Sync> Evaluator<G> for F {
+    fn evaluate(&self, game: &G) -> Vec<f32> {
+        self(game)
+    }
+}
+
+/// default policy used to play out games from the expanded node
+pub trait RolloutPolicy<G: Game>: Send + Sync + Debug {
+    /// index into `moves` of the move played next, `moves` is never empty
+    fn choose(&self, game: &G, moves: &[G::Move], rng: &mut dyn RngCore) -> usize;
+
+    /// scores of the players, ordered as in `Game::players`, that end the rollout after `depth`
+    /// moves have been played. none keeps playing
+    fn cutoff(&self, _game: &G, _depth: usize) -> Option<Vec<f32>> {
+        None
+    }
+}
+
+/// plays uniformly random moves
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Uniform;
+
+impl<G: Game> RolloutPolicy<G> for Uniform {
+    fn choose(&self, _game: &G, moves: &[G::Move], rng: &mut dyn RngCore) -> usize {
+        rng.gen_range(0..moves.len())
+    }
+}
+
+/// plays the move the heuristic rates highest, or a random move with probability `epsilon`
+pub struct EpsilonGreedy<F> {
+    pub epsilon: f32,
+    pub heuristic: F,
+}
+
+impl<F> Debug for EpsilonGreedy<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EpsilonGreedy").field("epsilon", &self.epsilon).finish_non_exhaustive()
+    }
+}
+
+impl<G: Game, F: Fn(&G, &G::Move) -> f32 + Send + Sync> RolloutPolicy<G> for EpsilonGreedy<F> {
+    fn choose(&self, game: &G, moves: &[G::Move], rng: &mut dyn RngCore) -> usize {
+        if rng.gen::<f32>() < self.epsilon {
+            return rng.gen_range(0..moves.len());
+        }
+        moves.iter()
+            .map(|m| (self.heuristic)(game, m))
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(0, |(i, _)| i)
+    }
+}
+
+/// plays moves with probability proportional to exp(weight / temperature)
+pub struct Softmax<F> {
+    pub temperature: f32,
+    pub weights: F,
+}
+
+impl<F> Debug for Softmax<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Softmax").field("temperature", &self.temperature).finish_non_exhaustive()
+    }
+}
+
+impl<G: Game, F: Fn(&G, &G::Move) -> f32 + Send + Sync> RolloutPolicy<G> for Softmax<F> {
+    fn choose(&self, game: &G, moves: &[G::Move], rng: &mut dyn RngCore) -> usize {
+        let weights = moves.iter().map(|m| (self.weights)(game, m)).collect::<Vec<_>>();
+        // shifted by the largest weight so exp does not overflow
+        let max = weights.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let temperature = self.temperature.max(f32::EPSILON);
+        match WeightedIndex::new(weights.iter().map(|w| ((w - max) / temperature).exp())) {
+            Ok(dist) => dist.sample(rng),
+            Err(_) => rng.gen_range(0..moves.len()),
+        }
+    }
+}
+
+/// plays with `policy` and ends the rollout after `depth` moves, scoring the reached game with
+/// `evaluate` (scores ordered as in `Game::players`)
+pub struct Cutoff<P, E> {
+    pub policy: P,
+    pub depth: usize,
+    pub evaluate: E,
+}
+
+impl<P: Debug, E> Debug for Cutoff<P, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cutoff").field("policy", &self.policy).field("depth", &self.depth).finish_non_exhaustive()
+    }
+}
+
+impl<G: Game, P: RolloutPolicy<G>, E: Evaluator<G>> RolloutPolicy<G> for Cutoff<P, E> {
+    fn choose(&self, game: &G, moves: &[G::Move], rng: &mut dyn RngCore) -> usize {
+        self.policy.choose(game, moves, rng)
+    }
+
+    fn cutoff(&self, game: &G, depth: usize) -> Option<Vec<f32>> {
+        if depth >= self.depth {
+            Some(self.evaluate.evaluate(game))
+        } else {
+            self.policy.cutoff(game, depth)
+        }
+    }
+}
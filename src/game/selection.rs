@@ -0,0 +1,182 @@
+use std::fmt::Debug;
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// statistics of a child node as seen by a selection policy
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ChildStats {
+    /// accumulated score of all visits
+    pub score: f32,
+    /// accumulated squared score of all visits, used for variance estimates
+    pub score_sq: f32,
+    pub visits: u32,
+    /// prior probability of the move leading to the child
+    pub prior: f32,
+}
+
+impl ChildStats {
+    pub fn mean(&self) -> f32 {
+        if self.visits == 0 { 0f32 } else { self.score / (self.visits as f32) }
+    }
+
+    /// sample variance of the child's scores
+    pub fn variance(&self) -> f32 {
+        if self.visits == 0 {
+            0f32
+        } else {
+            let mean = self.mean();
+            (self.score_sq / (self.visits as f32) - mean * mean).max(0f32)
+        }
+    }
+}
+
+/// range of node values, mean scores are mapped from it onto [0, 1] before selection so
+/// exploration constants mean the same in every game
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ValueBounds {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl ValueBounds {
+    pub fn new(min: f32, max: f32) -> Self {
+        Self { min, max }
+    }
+
+    /// smallest bounds containing both
+    pub fn union(self, other: Self) -> Self {
+        Self { min: self.min.min(other.min), max: self.max.max(other.max) }
+    }
+
+    /// statistics with every score mapped onto [0, 1], unchanged while the bounds are empty
+    pub fn normalize(&self, stats: ChildStats) -> ChildStats {
+        let range = self.max - self.min;
+        if range <= 0f32 {
+            return stats;
+        }
+        let n = stats.visits as f32;
+        ChildStats {
+            score: (stats.score - n * self.min) / range,
+            score_sq: (stats.score_sq - 2f32 * self.min * stats.score + n * self.min * self.min) / (range * range),
+            ..stats
+        }
+    }
+}
+
+/// tree policy used to pick which child is descended into during selection
+pub trait SelectionPolicy: Send + Sync + Debug {
+    /// value of a child, the child with the highest value is selected
+    fn value(&self, child: &ChildStats, parent_visits: u32, rng: &mut dyn RngCore) -> f32;
+}
+
+/// upper confidence bound applied to trees
+#[derive(Debug, Clone, Copy)]
+pub struct Ucb1 {
+    pub c: f32,
+}
+
+impl Default for Ucb1 {
+    fn default() -> Self {
+        Self { c: 2f32.sqrt() }
+    }
+}
+
+impl SelectionPolicy for Ucb1 {
+    fn value(&self, child: &ChildStats, parent_visits: u32, _rng: &mut dyn RngCore) -> f32 {
+        if child.visits == 0 {
+            f32::INFINITY
+        } else {
+            child.mean() + self.c * ((parent_visits as f32).ln() / (child.visits as f32)).sqrt()
+        }
+    }
+}
+
+/// ucb1 with the exploration term bounded by the observed variance (auer et al.)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ucb1Tuned;
+
+impl SelectionPolicy for Ucb1Tuned {
+    fn value(&self, child: &ChildStats, parent_visits: u32, _rng: &mut dyn RngCore) -> f32 {
+        if child.visits == 0 {
+            return f32::INFINITY;
+        }
+        let log_n = (parent_visits as f32).ln();
+        let n = child.visits as f32;
+        let v = child.variance() + (2f32 * log_n / n).sqrt();
+        child.mean() + (log_n / n * v.min(0.25)).sqrt()
+    }
+}
+
+/// variance aware ucb (audibert et al.), `range` is the width of the score interval
+#[derive(Debug, Clone, Copy)]
+pub struct UcbV {
+    pub c: f32,
+    pub zeta: f32,
+    pub range: f32,
+}
+
+impl Default for UcbV {
+    fn default() -> Self {
+        Self { c: 1f32, zeta: 1.2, range: 1f32 }
+    }
+}
+
+impl SelectionPolicy for UcbV {
+    fn value(&self, child: &ChildStats, parent_visits: u32, _rng: &mut dyn RngCore) -> f32 {
+        if child.visits == 0 {
+            return f32::INFINITY;
+        }
+        let e = self.zeta * (parent_visits as f32).ln();
+        let n = child.visits as f32;
+        child.mean() + (2f32 * child.variance() * e / n).sqrt() + self.c * 3f32 * self.range * e / n
+    }
+}
+
+/// predictor + ucb, exploration is weighted by the prior of each move
+#[derive(Debug, Clone, Copy)]
+pub struct Puct {
+    pub c: f32,
+}
+
+impl Default for Puct {
+    fn default() -> Self {
+        Self { c: 1.5 }
+    }
+}
+
+impl SelectionPolicy for Puct {
+    fn value(&self, child: &ChildStats, parent_visits: u32, _rng: &mut dyn RngCore) -> f32 {
+        child.mean() + self.c * child.prior * (parent_visits as f32).sqrt() / (1f32 + child.visits as f32)
+    }
+}
+
+/// samples each child's value from a gaussian around its mean score
+#[derive(Debug, Clone, Copy)]
+pub struct Thompson {
+    /// variance added to the observed variance, keeps rarely visited children exploring
+    pub prior_variance: f32,
+}
+
+impl Default for Thompson {
+    fn default() -> Self {
+        Self { prior_variance: 1f32 }
+    }
+}
+
+impl SelectionPolicy for Thompson {
+    fn value(&self, child: &ChildStats, _parent_visits: u32, rng: &mut dyn RngCore) -> f32 {
+        if child.visits == 0 {
+            return f32::INFINITY;
+        }
+        let z = standard_normal(rng);
+        let std_dev = ((child.variance() + self.prior_variance) / (child.visits as f32)).sqrt();
+        child.mean() + z * std_dev
+    }
+}
+
+/// box-muller
+pub(super) fn standard_normal(rng: &mut dyn RngCore) -> f32 {
+    let u1 = 1f32 - rng.gen::<f32>();
+    let u2 = rng.gen::<f32>();
+    (-2f32 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+}
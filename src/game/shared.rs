@@ -0,0 +1,411 @@
+use anyhow::Result;
+use hashbrown::HashMap;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::{collections::VecDeque, fmt::Debug, sync::{atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering}, Arc, Mutex, RwLock}, thread, time::Instant};
+
+use super::{
+    sample_outcome, ChildRange, ChildStats, Game, Mcts, MctsError, MctsNode, MctsTree, NodeId, Proof, SearchCounts,
+    SearchLimit, StateStats, ValueBounds,
+};
+
+/// f32 stored as bits, updated with compare and swap
+struct AtomicF32(AtomicU32);
+
+impl AtomicF32 {
+    fn new(v: f32) -> Self {
+        Self(AtomicU32::new(v.to_bits()))
+    }
+
+    fn load(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn fetch_add(&self, v: f32) {
+        let _ = self.0.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |b| Some((f32::from_bits(b) + v).to_bits()));
+    }
+}
+
+/// node of a tree walked by several threads at once
+struct SharedNode<Move> {
+    placement_move: Move,
+    score: AtomicF32,
+    score_sq: AtomicF32,
+    visits: AtomicU32,
+    /// number of simulations currently passing through this node
+    virtual_loss: AtomicU32,
+    availability: AtomicU32,
+    prior: f32,
+    chance: AtomicBool,
+    player: AtomicUsize,
+    children: RwLock<Vec<Arc<SharedNode<Move>>>>,
+    /// carried over from the tree unchanged, the tree parallel search does not update them
+    hash: Option<u64>,
+    state: StateStats,
+    amaf_score: f32,
+    amaf_visits: u32,
+    proof: Option<Proof>,
+}
+
+impl<Move: Default + Debug + Clone> SharedNode<Move> {
+    fn new(placement_move: Move, prior: f32) -> Self {
+        Self {
+            placement_move,
+            score: AtomicF32::new(0f32),
+            score_sq: AtomicF32::new(0f32),
+            visits: AtomicU32::new(0),
+            virtual_loss: AtomicU32::new(0),
+            availability: AtomicU32::new(1),
+            prior,
+            chance: AtomicBool::new(false),
+            player: AtomicUsize::new(0),
+            children: RwLock::new(Vec::new()),
+            hash: None,
+            state: StateStats::default(),
+            amaf_score: 0f32,
+            amaf_visits: 0,
+            proof: None,
+        }
+    }
+
+    /// statistics with every pending simulation counted as a visit scoring `loss`
+    fn stats(&self, loss: f32) -> ChildStats {
+        let pending = self.virtual_loss.load(Ordering::Relaxed);
+        ChildStats {
+            score: self.score.load() + loss * pending as f32,
+            score_sq: self.score_sq.load() + loss * loss * pending as f32,
+            visits: self.visits.load(Ordering::Relaxed) + pending,
+            prior: self.prior,
+        }
+    }
+
+    fn from_tree(tree: &MctsTree<Move>) -> Self {
+        // children always have larger ids than their parent, so build from the leaves upwards.
+        // transposed children lists end up under the last node sharing them, the others become
+        // leaves with a copy of the shared state statistics
+        let mut built: Vec<Option<Self>> = (0..tree.nodes.len()).map(|_| None).collect();
+        for id in (0..tree.nodes.len()).rev() {
+            let (n, stats) = (&tree.nodes[id], tree.stats[id]);
+            let canonical = &tree.nodes[*tree.canonical[id]];
+            let children = tree.child_ids(NodeId(id)).iter()
+                .filter_map(|c| built[**c].take().map(Arc::new))
+                .collect();
+            built[id] = Some(Self {
+                placement_move: n.placement_move.clone(),
+                score: AtomicF32::new(stats.score),
+                score_sq: AtomicF32::new(stats.score_sq),
+                visits: AtomicU32::new(stats.visits),
+                virtual_loss: AtomicU32::new(0),
+                availability: AtomicU32::new(n.availability),
+                prior: stats.prior,
+                chance: AtomicBool::new(n.chance),
+                player: AtomicUsize::new(n.player),
+                children: RwLock::new(children),
+                hash: n.hash,
+                state: canonical.state,
+                amaf_score: n.amaf_score,
+                amaf_visits: n.amaf_visits,
+                proof: canonical.proof,
+            });
+        }
+        built[0].take().unwrap()
+    }
+
+    fn into_tree(self) -> MctsTree<Move> {
+        let mut tree = MctsTree {
+            nodes: Vec::new(),
+            stats: Vec::new(),
+            edges: Vec::new(),
+            free_edges: HashMap::new(),
+            children: Vec::new(),
+            canonical: Vec::new(),
+            transpositions: HashMap::new(),
+            value_bounds: None,
+        };
+        let mut queue = VecDeque::from([Arc::new(self)]);
+        while let Some(node) = queue.pop_front() {
+            let children = node.children.read().unwrap();
+            let id = NodeId(tree.nodes.len());
+            let first_child = tree.nodes.len() + queue.len() + 1;
+            // leaves are merged again with the first node reaching their state, expanded nodes keep
+            // their own children
+            let canonical = match node.hash {
+                Some(hash) if children.is_empty() => *tree.transpositions.entry(hash).or_insert(id),
+                Some(hash) => {
+                    tree.transpositions.entry(hash).or_insert(id);
+                    id
+                },
+                None => id,
+            };
+            tree.canonical.push(canonical);
+            tree.children.push(ChildRange { start: tree.edges.len() as u32, len: children.len() as u32 });
+            tree.edges.extend((first_child..first_child + children.len()).map(NodeId));
+            queue.extend(children.iter().cloned());
+            tree.stats.push(node.stats(0f32));
+            tree.nodes.push(MctsNode {
+                placement_move: node.placement_move.clone(),
+                availability: node.availability.load(Ordering::Relaxed),
+                chance: node.chance.load(Ordering::Relaxed),
+                player: node.player.load(Ordering::Relaxed),
+                hash: node.hash,
+                state: node.state,
+                amaf_score: node.amaf_score,
+                amaf_visits: node.amaf_visits,
+                proof: node.proof,
+            });
+        }
+        tree
+    }
+}
+
+/// scores of every player after a move, in the order of `Game::players`
+#[derive(Debug, Clone)]
+struct PlayerScores {
+    values: Vec<f32>,
+    terminal: bool,
+}
+
+type Traversal<Move> = Vec<(Arc<SharedNode<Move>>, usize, PlayerScores)>;
+
+impl<G: Game> Mcts<G> {
+    /// tree parallel search, every thread walks the same tree. virtual loss is applied to nodes
+    /// with simulations in flight so threads spread out over different branches.
+    /// outcomes of chance nodes are always sampled, transpositions are not merged, no amaf
+    /// statistics are collected, policy value providers are not consulted, every child is
+    /// expanded at once without progressive widening and nothing is solved. every thread has
+    /// its own rng seeded from this search's rng, but results depend on thread scheduling.
+    /// the first error of any thread ends the search and is returned
+    pub fn try_best_move_tree_parallel(&mut self, base_game: &G, iterations: usize, threads: usize, retry_failed: bool) -> Result<G::Move, MctsError>
+    where
+        G: Sync,
+        G::Move: Send + Sync,
+        G::Player: Sync,
+    {
+        if base_game.possible_moves().is_empty() {
+            return Err(MctsError::NoLegalMoves);
+        }
+        self.players = base_game.players();
+        let tree = std::mem::replace(&mut self.tree, MctsTree::new());
+        let root = SharedNode::from_tree(&tree);
+        // every thread tracks the values it backs up, merged once the search is done
+        let value_bounds = Mutex::new(tree.value_bounds);
+        let limit = SearchLimit { iterations, deadline: None, stop: self.stop.clone() };
+        let start = Instant::now();
+        let claimed = AtomicUsize::new(0);
+        let completed = AtomicUsize::new(0);
+        let failed = AtomicUsize::new(0);
+        // first error that ends the search, the other threads stop at their next iteration
+        let error = Mutex::new(None);
+        let aborted = AtomicBool::new(false);
+        let max_retries = self.config.max_retries;
+        let seeds = (0..threads.max(1)).map(|_| self.rng.gen::<u64>()).collect::<Vec<_>>();
+
+        thread::scope(|scope| {
+            for seed in seeds {
+                let (root, limit, claimed, completed, failed) = (&root, &limit, &claimed, &completed, &failed);
+                let (error, aborted, value_bounds) = (&error, &aborted, &value_bounds);
+                let this = &*self;
+                scope.spawn(move || {
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    let mut bounds = *value_bounds.lock().unwrap();
+                    // always complete one iteration so a move can be picked
+                    loop {
+                        let i = claimed.fetch_add(1, Ordering::Relaxed);
+                        if aborted.load(Ordering::Relaxed) || (i > 0 && !limit.proceed(i)) {
+                            break;
+                        }
+                        match this.simulate_shared(root, base_game, &mut bounds, &mut rng) {
+                            Ok(()) => {
+                                completed.fetch_add(1, Ordering::Relaxed);
+                            },
+                            Err(e) => {
+                                let failures = failed.fetch_add(1, Ordering::Relaxed) + 1;
+                                claimed.fetch_sub(1, Ordering::Relaxed);
+                                if !retry_failed || failures > max_retries {
+                                    let e = if retry_failed {
+                                        MctsError::RetriesExhausted { failed: failures, last: e }
+                                    } else {
+                                        MctsError::Game(e)
+                                    };
+                                    error.lock().unwrap().get_or_insert(e);
+                                    aborted.store(true, Ordering::Relaxed);
+                                    break;
+                                }
+                            },
+                        }
+                    }
+                    let mut merged = value_bounds.lock().unwrap();
+                    *merged = match (*merged, bounds) {
+                        (Some(a), Some(b)) => Some(a.union(b)),
+                        (a, b) => a.or(b),
+                    };
+                });
+            }
+        });
+        self.stop.finish();
+        self.last_search = SearchCounts {
+            iterations: completed.into_inner(),
+            failed: failed.into_inner(),
+            elapsed: start.elapsed(),
+        };
+
+        self.tree = root.into_tree();
+        self.tree.value_bounds = value_bounds.into_inner().unwrap();
+        if let Some(e) = error.into_inner().unwrap() {
+            return Err(e);
+        }
+        self.final_move(base_game)
+    }
+
+    /// tree parallel search, panics if the search fails (see `try_best_move_tree_parallel`)
+    pub fn best_move_tree_parallel(&mut self, base_game: &G, iterations: usize, threads: usize, retry_failed: bool) -> G::Move
+    where
+        G: Sync,
+        G::Move: Send + Sync,
+        G::Player: Sync,
+    {
+        self.try_best_move_tree_parallel(base_game, iterations, threads, retry_failed).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    fn score_players(&self, game: &G, state: G::GameState) -> PlayerScores {
+        let mut terminal = false;
+        let values = self.players.iter()
+            .map(|p| {
+                let score = game.score_state(state.clone(), p.clone());
+                terminal |= score.is_terminal();
+                score.score()
+            })
+            .collect();
+        PlayerScores { values, terminal }
+    }
+
+    /// `bounds` holds the values backed up by the calling thread
+    fn simulate_shared(&self, root: &SharedNode<G::Move>, base_game: &G, bounds: &mut Option<ValueBounds>, rng: &mut impl Rng) -> Result<()> {
+        let mut game = self.iteration_game(base_game, rng);
+        let mut traversal = Vec::new();
+        let selection_bounds = self.selection_bounds(&game, *bounds);
+        let result = self.select_shared(root, &mut game, &mut traversal, selection_bounds, rng)
+            .and_then(|_| match traversal.last() {
+                Some((_, _, s)) if s.terminal => Ok(vec![0f32; self.players.len()]),
+                _ => self.rollout(&mut game, None, rng),
+            });
+
+        if result.is_ok() {
+            root.visits.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut acc_score = result.as_ref().ok().cloned();
+        for (node, mover, scores) in traversal.iter().rev() {
+            node.virtual_loss.fetch_sub(1, Ordering::Relaxed);
+            if let Some(acc_score) = acc_score.as_mut() {
+                for (acc, s) in acc_score.iter_mut().zip(scores.values.iter()) {
+                    *acc += s;
+                }
+                let value = self.node_value(acc_score, *mover);
+                let observed = ValueBounds::new(value, value);
+                *bounds = Some(bounds.map_or(observed, |b| b.union(observed)));
+                node.visits.fetch_add(1, Ordering::Relaxed);
+                node.score.fetch_add(value);
+                node.score_sq.fetch_add(value * value);
+            }
+        }
+        result.map(|_| ())
+    }
+
+    /// selects and expands, nodes are pushed onto `traversal` (without the root) with virtual loss applied
+    fn select_shared(&self, root: &SharedNode<G::Move>, game: &mut G, traversal: &mut Traversal<G::Move>, bounds: Option<ValueBounds>, rng: &mut impl Rng) -> Result<()> {
+        let loss = self.config.virtual_loss;
+        loop {
+            let node = traversal.last().map_or(root, |(n, _, _)| &**n);
+            let outcomes = game.chance_outcomes();
+            let legal = (!G::IS_PERFECT_INFORMATION).then(|| game.possible_moves());
+
+            let mut drawn = None;
+            let selected = if !outcomes.is_empty() {
+                node.chance.store(true, Ordering::Relaxed);
+                let outcome = sample_outcome(&outcomes, rng)?.0.clone();
+                let child = node.children.read().unwrap().iter()
+                    .find(|c| c.placement_move == outcome)
+                    .cloned();
+                drawn = Some(outcome);
+                child
+            } else {
+                let children = node.children.read().unwrap();
+                let candidates = children.iter()
+                    .filter(|c| legal.as_ref().is_none_or(|l| l.contains(&c.placement_move)))
+                    .collect::<Vec<_>>();
+                let fully_expanded = !children.is_empty()
+                    && legal.as_ref().is_none_or(|l| l.len() == candidates.len());
+                if !fully_expanded {
+                    None
+                } else {
+                    let parent_visits = node.visits.load(Ordering::Relaxed) + node.virtual_loss.load(Ordering::Relaxed);
+                    candidates.into_iter()
+                        .map(|c| {
+                            let parent_visits = if G::IS_PERFECT_INFORMATION {
+                                parent_visits
+                            } else {
+                                c.availability.fetch_add(1, Ordering::Relaxed) + 1
+                            };
+                            let stats = bounds.map_or(c.stats(loss), |b| b.normalize(c.stats(loss)));
+                            (c, self.config.selection_policy.value(&stats, parent_visits, rng))
+                        })
+                        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                        .map(|(c, _)| c.clone())
+                }
+            };
+
+            let (child, expanded) = match (selected, drawn) {
+                (Some(child), _) => (child, false),
+                // outcomes of chance nodes are sampled, add the drawn outcome if it is missing
+                (None, Some(outcome)) => {
+                    let mut children = node.children.write().unwrap();
+                    let existing = children.iter().find(|c| c.placement_move == outcome).cloned();
+                    let child = existing.unwrap_or_else(|| {
+                        let p = outcomes.iter().find(|(m, _)| *m == outcome).unwrap().1;
+                        let child = Arc::new(SharedNode::new(outcome, p));
+                        children.push(child.clone());
+                        child
+                    });
+                    (child, true)
+                },
+                (None, None) => {
+                    let moves = legal.unwrap_or_else(|| game.possible_moves());
+                    let mut children = node.children.write().unwrap();
+                    // another thread may have expanded this node in the meantime
+                    let missing = moves.iter()
+                        .filter(|m| !children.iter().any(|c| c.placement_move == **m))
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    let prior = 1f32 / (moves.len() as f32);
+                    let added = missing.len();
+                    children.extend(missing.into_iter().map(|m| Arc::new(SharedNode::new(m, prior))));
+                    let child = if added > 0 {
+                        children[children.len() - added..].choose(rng)
+                    } else {
+                        children.iter()
+                            .filter(|c| moves.contains(&c.placement_move))
+                            .collect::<Vec<_>>()
+                            .choose(rng)
+                            .copied()
+                    };
+                    let Some(child) = child else {
+                        break;
+                    };
+                    (child.clone(), true)
+                },
+            };
+
+            child.virtual_loss.fetch_add(1, Ordering::Relaxed);
+            let mover = self.player_index(&game.current_player());
+            child.player.store(mover, Ordering::Relaxed);
+            traversal.push((child.clone(), mover, PlayerScores { values: Vec::new(), terminal: false }));
+            let s = game.place_move(child.placement_move.clone())?;
+            let scores = self.score_players(game, s);
+            let terminal = scores.terminal;
+            traversal.last_mut().unwrap().2 = scores;
+            if expanded || terminal {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
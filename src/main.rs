@@ -1,5 +1,12 @@
-mod game;
+// game modules are only exercised by the tests below
+#[cfg(test)]
+use mcts::game;
+
+#[cfg(test)]
+mod pig;
+#[cfg(test)]
 mod tictactoe;
+#[cfg(test)]
 mod uno;
 
 fn main() {
@@ -33,41 +40,973 @@ fn main() {
 
 #[cfg(test)]
 mod tests {
-    #[cfg(not(target_env = "msvc"))]
-    use tikv_jemallocator::Jemalloc;
-
-    use std::{fs::File, io::Write};
+    use std::{fs::File, io::Write, thread, time::{Duration, Instant}};
 
     use rayon::prelude::*;
-    use crate::{game::{Game, Mcts}, uno::{self, Uno}};
-    use rand::seq::IteratorRandom;
+    use crate::{
+        game::{
+            Backup, BudgetAction, ChanceMode, ChildStats, Cutoff, DirichletNoise, DotOptions, EpsilonGreedy,
+            FinalMoveSelection, Game, Mcts, MctsError, MoveScore, PolicyValue, ProgressiveWidening, Proof, Puct,
+            RolloutPolicy, SelectionPolicy, Softmax, Thompson, TreeBudget, Ucb1, Ucb1Tuned, UcbV, Uniform, ValueBounds,
+        },
+        pig::{Pig, PigMove},
+        tictactoe::TicTacToe,
+        uno::{self, Uno},
+    };
+    use rand::{rngs::StdRng, seq::IteratorRandom, Rng, RngCore, SeedableRng};
+
+    /// first player to move, can win immediately by placing at 2
+    fn tictactoe_one_move_win() -> TicTacToe {
+        let mut game = TicTacToe::new();
+        for m in [0, 3, 1, 4] {
+            game.place_move(m).unwrap();
+        }
+        game
+    }
+
+    /// second player threatens 3-4-5, first player has no win and has to block at 5
+    fn tictactoe_forced_block() -> TicTacToe {
+        let mut game = TicTacToe::new();
+        for m in [0, 3, 8, 4] {
+            game.place_move(m).unwrap();
+        }
+        game
+    }
+
+    /// both players are two points short of 20, holding the turn total of 3 wins
+    fn pig_hold_to_win() -> Pig {
+        let mut game = Pig::new(2, 20);
+        game.scores = vec![18, 18];
+        game.turn_total = 3;
+        game
+    }
+
+    fn finds_immediate_win(policy: impl SelectionPolicy + 'static) -> usize {
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::builder(game.first_player_turn)
+            .selection_policy(policy)
+            .build();
+        bot.best_move(&game, 1000, false)
+    }
+
+    #[test]
+    fn selection_policies_find_immediate_win() {
+        assert_eq!(finds_immediate_win(Ucb1::default()), 2);
+        assert_eq!(finds_immediate_win(Ucb1 { c: 0.5 }), 2);
+        assert_eq!(finds_immediate_win(Ucb1Tuned), 2);
+        assert_eq!(finds_immediate_win(UcbV::default()), 2);
+        assert_eq!(finds_immediate_win(Puct::default()), 2);
+        assert_eq!(finds_immediate_win(Thompson::default()), 2);
+    }
+
+    #[test]
+    fn rollout_policies() {
+        let game = tictactoe_one_move_win();
+        let centre_first = |_: &TicTacToe, m: &usize| if *m == 4 { 1.0 } else { 0.0 };
+        let mut bots = [
+            Mcts::builder(game.first_player_turn).rollout_policy(Uniform).build(),
+            Mcts::builder(game.first_player_turn).rollout_policy(EpsilonGreedy { epsilon: 0.2, heuristic: centre_first }).build(),
+            Mcts::builder(game.first_player_turn).rollout_policy(Softmax { temperature: 0.5, weights: centre_first }).build(),
+            Mcts::builder(game.first_player_turn)
+                .rollout_policy(Cutoff { policy: Uniform, depth: 2, evaluate: |_: &TicTacToe| vec![0.5, 0.5] })
+                .build(),
+        ];
+        for bot in bots.iter_mut() {
+            assert_eq!(bot.best_move(&game, 1000, false), 2);
+        }
+
+        // the policies on their own, greedy and cold softmax always play the top weighted move
+        let moves = game.possible_moves();
+        let top = moves.iter().position(|m| *m == 7).unwrap();
+        let seven_first = |_: &TicTacToe, m: &usize| if *m == 7 { 1.0 } else { 0.0 };
+        let greedy = EpsilonGreedy { epsilon: 0.0, heuristic: seven_first };
+        let softmax = Softmax { temperature: 0.01, weights: seven_first };
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..100 {
+            assert_eq!(greedy.choose(&game, &moves, &mut rng), top);
+            assert_eq!(softmax.choose(&game, &moves, &mut rng), top);
+        }
+        let cutoff = Cutoff { policy: Uniform, depth: 2, evaluate: |_: &TicTacToe| vec![0.25, 0.75] };
+        assert_eq!(cutoff.cutoff(&game, 1), None);
+        assert_eq!(cutoff.cutoff(&game, 2), Some(vec![0.25, 0.75]));
+
+        // dump high value cards first during playouts
+        let game = Uno::standard_deck(2);
+        let mut bot = Mcts::builder(game.player_turn)
+            .information_set(true)
+            .rollout_policy(EpsilonGreedy { epsilon: 0.1, heuristic: |_: &Uno, m: &uno::PlayerMove| m.points() as f32 })
+            .build();
+        assert!(game.possible_moves().contains(&bot.best_move(&game, 64, true)));
+    }
+
+    #[test]
+    fn truncated_rollouts() {
+        // no playouts at all, only terminal states and the evaluation guide the search
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::builder(game.first_player_turn).evaluator(|_: &TicTacToe| vec![0.5, 0.5], 0).build();
+        assert_eq!(bot.best_move(&game, 1000, false), 2);
+
+        let game = tictactoe_forced_block();
+        let mut bot = Mcts::builder(game.first_player_turn).evaluator(|_: &TicTacToe| vec![0.5, 0.5], 2).build();
+        assert_eq!(bot.best_move(&game, 2000, false), 5);
+    }
+
+    /// prefers the centre, values every position as even
+    struct CentrePrior;
+
+    impl PolicyValue<TicTacToe> for CentrePrior {
+        fn evaluate(&self, _game: &TicTacToe, moves: &[usize]) -> (Vec<f32>, Vec<f32>) {
+            let weights = moves.iter().map(|m| if *m == 4 { 3.0 } else { 1.0 }).collect::<Vec<f32>>();
+            let total = weights.iter().sum::<f32>();
+            (weights.iter().map(|w| w / total).collect(), vec![0.5, 0.5])
+        }
+    }
+
+    /// values every pig position as even
+    struct EvenPig;
+
+    impl PolicyValue<Pig> for EvenPig {
+        fn evaluate(&self, _game: &Pig, moves: &[PigMove]) -> (Vec<f32>, Vec<f32>) {
+            (vec![1.0 / moves.len().max(1) as f32; moves.len()], vec![0.5, 0.5])
+        }
+    }
+
+    /// fails the test if a rollout is played
+    #[derive(Debug)]
+    struct NoRollouts;
+
+    impl<G: Game> RolloutPolicy<G> for NoRollouts {
+        fn choose(&self, _game: &G, _moves: &[G::Move], _rng: &mut dyn RngCore) -> usize {
+            panic!("rollout played with a policy value provider");
+        }
+    }
+
+    #[test]
+    fn policy_value_search() {
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::builder(game.first_player_turn).policy_value(CentrePrior).build();
+        assert_eq!(bot.best_move(&game, 1000, false), 2);
+
+        let mut bot = Mcts::builder(game.first_player_turn)
+            .policy_value(CentrePrior)
+            .selection_policy(Puct { c: 2.0 })
+            .root_noise(DirichletNoise::default())
+            .build();
+        assert_eq!(bot.best_move(&game, 1000, false), 2);
+        assert!(bot.advance(&2));
+
+        // drawn chance outcomes and leaves beyond the tree budget are valued by the provider too
+        let game = pig_hold_to_win();
+        let mut bot = Mcts::builder(game.player_turn).policy_value(EvenPig).rollout_policy(NoRollouts).build();
+        bot.best_move(&game, 500, false);
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::builder(game.first_player_turn)
+            .policy_value(CentrePrior)
+            .rollout_policy(NoRollouts)
+            .tree_budget(TreeBudget::Nodes(10), BudgetAction::StopExpanding)
+            .build();
+        bot.best_move(&game, 500, false);
+    }
+
+    #[test]
+    fn opponents_play_for_themselves() {
+        let game = tictactoe_forced_block();
+        for backup in [Backup::MaxN, Backup::Paranoid, Backup::Negamax] {
+            let mut bot = Mcts::builder(game.first_player_turn).backup(backup).build();
+            assert_eq!(bot.best_move(&game, 3000, false), 5);
+        }
+    }
+
+    #[test]
+    fn multi_player_search() {
+        let game = Pig::new(3, 20);
+        assert_eq!(game.num_players(), 3);
+        assert_eq!(game.current_player(), 0);
+        let mut bot = Mcts::new(game.player_turn);
+        assert!(game.possible_moves().contains(&bot.best_move(&game, 500, false)));
+    }
+
+    #[test]
+    fn search_report() {
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::new(game.first_player_turn);
+        assert!(bot.report(&game).is_none());
+        bot.best_move(&game, 1000, false);
+        let report = bot.report(&game).unwrap();
+        assert_eq!(report.best_move, 2);
+        assert_eq!(report.principal_variation.first(), Some(&2));
+        assert_eq!(report.children.len(), game.possible_moves().len());
+        assert_eq!(report.children.iter().map(|c| c.visits as usize).sum::<usize>(), 1000);
+        assert_eq!((report.iterations, report.failed_simulations), (1000, 0));
+        assert!(report.max_depth >= 1 && report.nodes > report.children.len());
+
+        bot.best_move_tree_parallel(&game, 500, 2, false);
+        assert_eq!(bot.report(&game).unwrap().iterations, 500);
+    }
+
+    /// tic tac toe where placing a `broken` move fails
+    #[derive(Debug, Clone)]
+    struct Faulty {
+        game: TicTacToe,
+        broken: fn(&TicTacToe, usize) -> bool,
+    }
+
+    impl Game for Faulty {
+        const IS_PERFECT_INFORMATION: bool = true;
+
+        type Move = usize;
+        type GameState = <TicTacToe as Game>::GameState;
+        type Player = bool;
+
+        fn possible_moves(&self) -> Vec<usize> {
+            self.game.possible_moves()
+        }
+
+        fn place_move(&mut self, movement: usize) -> anyhow::Result<Self::GameState> {
+            if (self.broken)(&self.game, movement) {
+                anyhow::bail!("broken move {movement}");
+            }
+            self.game.place_move(movement)
+        }
+
+        fn score_state(&self, state: Self::GameState, player: bool) -> MoveScore {
+            self.game.score_state(state, player)
+        }
+
+        fn current_player(&self) -> bool {
+            self.game.current_player()
+        }
+
+        fn players(&self) -> Vec<bool> {
+            self.game.players()
+        }
+    }
+
+    /// pig whose dice have no face with a positive probability
+    #[derive(Debug, Clone)]
+    struct Loaded(Pig);
+
+    impl Game for Loaded {
+        const IS_PERFECT_INFORMATION: bool = true;
+
+        type Move = PigMove;
+        type GameState = <Pig as Game>::GameState;
+        type Player = usize;
+
+        fn possible_moves(&self) -> Vec<PigMove> {
+            self.0.possible_moves()
+        }
+
+        fn place_move(&mut self, movement: PigMove) -> anyhow::Result<Self::GameState> {
+            self.0.place_move(movement)
+        }
+
+        fn score_state(&self, state: Self::GameState, player: usize) -> MoveScore {
+            self.0.score_state(state, player)
+        }
+
+        fn current_player(&self) -> usize {
+            self.0.current_player()
+        }
+
+        fn players(&self) -> Vec<usize> {
+            self.0.players()
+        }
+
+        fn chance_outcomes(&self) -> Vec<(PigMove, f32)> {
+            self.0.chance_outcomes().into_iter().map(|(m, _)| (m, 0.0)).collect()
+        }
+    }
+
+    /// values positions for a single player in a two player game
+    struct ShortValue;
+
+    impl PolicyValue<TicTacToe> for ShortValue {
+        fn evaluate(&self, _game: &TicTacToe, moves: &[usize]) -> (Vec<f32>, Vec<f32>) {
+            (vec![1.0 / moves.len() as f32; moves.len()], vec![0.5])
+        }
+    }
+
+    #[test]
+    fn failed_searches_return_errors() {
+        let always = Faulty { game: TicTacToe::new(), broken: |_, _| true };
+        let mut bot = Mcts::builder(true).max_retries(10).build();
+        assert!(matches!(bot.try_best_move(&always, 100, false), Err(MctsError::Game(_))));
+        assert!(matches!(bot.try_best_move(&always, 100, true), Err(MctsError::RetriesExhausted { failed: 11, .. })));
+
+        let mut full = TicTacToe::new();
+        for m in [0, 1, 2, 4, 3, 5, 7, 6, 8] {
+            full.place_move(m).unwrap();
+        }
+        let mut bot = Mcts::new(true);
+        assert!(matches!(bot.try_best_move(&full, 100, true), Err(MctsError::NoLegalMoves)));
+        assert!(matches!(bot.try_best_move_for(&full, Duration::from_millis(10), true), Err(MctsError::NoLegalMoves)));
+        assert!(matches!(bot.try_best_move_parallel(&full, 100, 2, true), Err(MctsError::NoLegalMoves)));
+        assert!(matches!(bot.try_best_move_tree_parallel(&full, 100, 2, true), Err(MctsError::NoLegalMoves)));
+
+        // invalid chance outcomes and provider results fail the simulation
+        let loaded = Loaded(Pig::new(2, 20));
+        let mut bot = Mcts::builder(0).max_retries(10).build();
+        assert!(matches!(bot.try_best_move(&loaded, 100, false), Err(MctsError::Game(_))));
+        assert!(matches!(bot.try_best_move(&loaded, 100, true), Err(MctsError::RetriesExhausted { .. })));
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::builder(game.first_player_turn).policy_value(ShortValue).build();
+        assert!(matches!(bot.try_best_move(&game, 100, false), Err(MctsError::Game(_))));
+
+        // parallel searches report failures of any thread instead of panicking
+        let mut bot = Mcts::builder(true).max_retries(10).build();
+        assert!(matches!(bot.try_best_move_parallel(&always, 100, 2, false), Err(MctsError::Game(_))));
+        assert!(matches!(bot.try_best_move_tree_parallel(&always, 100, 2, true), Err(MctsError::RetriesExhausted { .. })));
+
+        // failures deep in rollouts stay below the retry budget, they are counted but a move is found
+        let corner = Faulty { game: tictactoe_one_move_win(), broken: |g, m| m == 8 && g.possible_moves().len() <= 2 };
+        let mut bot = Mcts::builder(corner.current_player()).seed(7).build();
+        assert_eq!(bot.try_best_move(&corner, 300, true).unwrap(), 2);
+        let report = bot.report(&corner).unwrap();
+        assert_eq!(report.iterations, 300);
+        assert!(report.failed_simulations > 0);
+    }
+
+    #[test]
+    fn export_dot() {
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::new(game.first_player_turn);
+        bot.best_move(&game, 500, false);
+
+        let mut out = Vec::new();
+        bot.export_dot(&mut out, &DotOptions::default()).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+        assert!(dot.starts_with("digraph") && dot.contains("-> ") && dot.contains("[penwidth=3]"));
+
+        let mut out = Vec::new();
+        bot.export_dot(&mut out, &DotOptions { max_depth: Some(1), top_k: Some(2), ..Default::default() }).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().matches(" -> ").count(), 2);
+    }
+
+    #[test]
+    fn json_tree_round_trip() {
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::new(game.first_player_turn);
+        bot.best_move(&game, 500, false);
+        let mut json = Vec::new();
+        bot.export_json(&mut json).unwrap();
+
+        let mut loaded = Mcts::new(game.first_player_turn);
+        loaded.import_json(json.as_slice()).unwrap();
+        let visits = |bot: &Mcts<TicTacToe>| bot.report(&game).unwrap().children.iter().map(|c| (c.placement_move, c.visits)).collect::<Vec<_>>();
+        assert_eq!(visits(&bot), visits(&loaded));
+        // the loaded tree warm starts the next search
+        assert_eq!(loaded.best_move(&game, 1, false), 2);
+
+        assert!(loaded.import_json(&b"{\"nodes\":[],\"children\":[],\"canonical\":[]}"[..]).is_err());
+
+        // children lists reaching past the edges are rejected, including ones overflowing u32
+        let mut tree: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        tree["children"][0] = serde_json::json!({ "start": u32::MAX, "len": 1 });
+        assert!(loaded.import_json(tree.to_string().as_bytes()).is_err());
+    }
+
+    #[test]
+    fn final_move_selection() {
+        let game = tictactoe_one_move_win();
+        for selection in [
+            FinalMoveSelection::MaxChild,
+            FinalMoveSelection::RobustChild,
+            FinalMoveSelection::MaxRobustChild { extra_iterations: 1000 },
+            FinalMoveSelection::SecureChild { c: 1.0 },
+            FinalMoveSelection::Temperature(0.01),
+        ] {
+            let mut bot = Mcts::builder(game.first_player_turn).final_move_selection(selection).build();
+            assert_eq!(bot.best_move(&game, 1000, false), 2, "{selection:?}");
+        }
+
+        let mut bot = Mcts::builder(game.first_player_turn).final_move_selection(FinalMoveSelection::Temperature(1.0)).build();
+        assert!(game.possible_moves().contains(&bot.best_move(&game, 100, false)));
+    }
+
+    /// a single move out of ten, every one of them losing points
+    #[derive(Debug, Clone, Default)]
+    struct Penalty {
+        chosen: Option<usize>,
+    }
+
+    impl Game for Penalty {
+        const IS_PERFECT_INFORMATION: bool = true;
+
+        type Move = usize;
+        type GameState = ();
+        type Player = usize;
+
+        fn possible_moves(&self) -> Vec<usize> {
+            if self.chosen.is_some() { Vec::new() } else { (0..10).collect() }
+        }
+
+        fn place_move(&mut self, movement: usize) -> anyhow::Result<()> {
+            self.chosen = Some(movement);
+            Ok(())
+        }
+
+        fn score_state(&self, _state: (), _player: usize) -> MoveScore {
+            match self.chosen {
+                Some(m) => MoveScore::Terminal(-((10 - m) as f32) / 10f32),
+                None => MoveScore::None,
+            }
+        }
+
+        fn current_player(&self) -> usize {
+            0
+        }
+
+        fn players(&self) -> Vec<usize> {
+            vec![0]
+        }
+    }
+
+    #[test]
+    fn final_move_selection_skips_unvisited_children() {
+        // after 3 iterations most children are unvisited, their mean of 0 beats every visited child
+        let game = Penalty::default();
+        for selection in [FinalMoveSelection::MaxChild, FinalMoveSelection::SecureChild { c: 0.1 }] {
+            let mut bot = Mcts::builder(0).final_move_selection(selection).seed(3).build();
+            let bot_move = bot.best_move(&game, 3, false);
+            let report = bot.report(&game).unwrap();
+            let best = report.children.iter()
+                .filter(|c| c.visits > 0)
+                .max_by(|a, b| a.mean.total_cmp(&b.mean))
+                .unwrap();
+            assert_eq!(bot_move, best.placement_move, "{selection:?}");
+        }
+
+        // the best visited child is also among the most visited, so the search is not extended
+        let mut bot = Mcts::builder(0).final_move_selection(FinalMoveSelection::MaxRobustChild { extra_iterations: 100 }).seed(3).build();
+        bot.best_move(&game, 3, false);
+        assert_eq!(bot.report(&game).unwrap().iterations, 3);
+    }
+
+    #[test]
+    fn time_budgeted_search() {
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::new(game.first_player_turn);
+        let start = Instant::now();
+        assert_eq!(bot.best_move_for(&game, Duration::from_millis(50), false), 2);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn stop_handle_ends_search() {
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::new(game.first_player_turn);
+        let stop = bot.stop_handle();
+        let stopper = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            stop.stop();
+        });
+        assert_eq!(bot.best_move(&game, usize::MAX, false), 2);
+        stopper.join().unwrap();
+        assert!(!bot.stop_handle().is_stopped());
+        assert_eq!(bot.best_move(&game, 2000, false), 2);
+        assert_eq!(bot.report(&game).unwrap().iterations, 2000);
+
+        // a stop sent before the search starts is not lost, and only ends that search
+        for threads in [None, Some(2)] {
+            bot.stop_handle().stop();
+            assert!(bot.stop_handle().is_stopped());
+            match threads {
+                Some(threads) => bot.best_move_parallel(&game, usize::MAX, threads, false),
+                None => bot.best_move(&game, usize::MAX, false),
+            };
+            assert!(bot.report(&game).unwrap().iterations <= 2);
+            assert!(!bot.stop_handle().is_stopped());
+            bot.best_move_tree_parallel(&game, 2000, 2, false);
+            assert_eq!(bot.report(&game).unwrap().iterations, 2000);
+        }
+    }
+
+    #[test]
+    fn root_parallel_search() {
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::new(game.first_player_turn);
+        assert_eq!(bot.best_move_parallel(&game, 2000, 4, false), 2);
+        let report = bot.report(&game).unwrap();
+        assert_eq!(report.iterations, 2000);
+        // the other threads' visits are merged into the calling thread's tree
+        let visits = report.children.iter().map(|c| c.visits).sum::<u32>();
+        assert_eq!(visits, 2000);
+        assert!(visits > 2000 / 4);
+        // merged root statistics are kept for the next move
+        assert!(bot.advance(&2));
+    }
+
+    /// plays uniformly random moves slowly, so the simulations of different threads overlap
+    #[derive(Debug)]
+    struct SlowRollouts;
+
+    impl<G: Game> RolloutPolicy<G> for SlowRollouts {
+        fn choose(&self, _game: &G, moves: &[G::Move], rng: &mut dyn RngCore) -> usize {
+            thread::sleep(Duration::from_micros(200));
+            rng.gen_range(0..moves.len())
+        }
+    }
+
+    #[test]
+    fn tree_parallel_search() {
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::builder(game.first_player_turn)
+            .virtual_loss(-3.0)
+            .build();
+        assert_eq!(bot.best_move_tree_parallel(&game, 2000, 4, false), 2);
+        let report = bot.report(&game).unwrap();
+        assert_eq!(report.iterations, 2000);
+        assert_eq!(report.children.iter().map(|c| c.visits).sum::<u32>(), 2000);
+        assert!(bot.advance(&2));
+
+        // a greedy search piles onto one child unless simulations in flight push threads away
+        let game = TicTacToe::new();
+        let most_visited = |virtual_loss| (0..3)
+            .map(|_| {
+                let mut bot = Mcts::builder(game.first_player_turn)
+                    .selection_policy(Ucb1 { c: 0.0 })
+                    .rollout_policy(SlowRollouts)
+                    .virtual_loss(virtual_loss)
+                    .build();
+                bot.best_move_tree_parallel(&game, 400, 4, false);
+                bot.report(&game).unwrap().children.iter().map(|c| c.visits).max().unwrap()
+            })
+            .sum::<u32>();
+        assert!(most_visited(-100.0) < most_visited(100.0));
+
+        // proofs and statistics already in the tree are kept
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::builder(game.first_player_turn).solver(true).build();
+        bot.best_move(&game, 1000, false);
+        assert_eq!(bot.best_move_tree_parallel(&game, 100, 2, false), 2);
+        assert_eq!(bot.report(&game).unwrap().proof, Some(Proof::Win(0)));
+    }
+
+    #[test]
+    fn advance_reuses_subtree() {
+        let mut game = TicTacToe::new();
+        let mut bot = Mcts::new(game.first_player_turn);
+        let first = bot.best_move(&game, 500, false);
+        game.place_move(first).unwrap();
+        assert!(bot.advance(&first));
+
+        // opponent reply, every reply was expanded below the played move
+        let reply = *game.possible_moves().first().unwrap();
+        game.place_move(reply).unwrap();
+        assert!(bot.advance(&reply));
+        let next = bot.best_move(&game, 200, false);
+        assert!(game.possible_moves().contains(&next));
+
+        // moves that were never expanded reset the tree
+        let mut fresh = Mcts::<TicTacToe>::new(true);
+        assert!(!fresh.advance(&4));
+    }
+
+    #[test]
+    fn tree_budget() {
+        let game = tictactoe_one_move_win();
+        for action in [BudgetAction::StopExpanding, BudgetAction::Prune] {
+            let mut bot = Mcts::builder(game.first_player_turn).tree_budget(TreeBudget::Nodes(40), action).build();
+            assert_eq!(bot.best_move(&game, 2000, false), 2);
+            assert!(bot.report(&game).unwrap().nodes <= 40);
+        }
+
+        let mut bot = Mcts::builder(true).tree_budget(TreeBudget::Bytes(64 * 1024), BudgetAction::Prune).build();
+        let mut game = TicTacToe::new();
+        while !game.possible_moves().is_empty() && !game.game_ended {
+            let m = bot.best_move(&game, 500, false);
+            assert!(bot.memory_usage() <= 64 * 1024);
+            game.place_move(m).unwrap();
+            bot.advance(&m);
+        }
+
+        // outcomes of chance nodes that do not fit are left to the rollout
+        for mode in [ChanceMode::Sample, ChanceMode::Enumerate] {
+            let game = pig_hold_to_win();
+            let mut bot = Mcts::builder(game.player_turn)
+                .chance_mode(mode)
+                .tree_budget(TreeBudget::Nodes(20), BudgetAction::Prune)
+                .build();
+            assert_eq!(bot.best_move(&game, 1000, false), PigMove::Hold);
+            assert!(bot.report(&game).unwrap().nodes <= 20);
+        }
+    }
+
+    #[test]
+    fn progressive_widening() {
+        let game = tictactoe_one_move_win();
+        let widening = ProgressiveWidening { k: 1.0, alpha: 0.5 };
+        let mut bot = Mcts::builder(game.first_player_turn)
+            .progressive_widening(widening)
+            .move_ordering(|_: &TicTacToe, m: &usize| if *m == 2 { 1.0 } else { 0.0 })
+            .seed(1)
+            .build();
+        assert_eq!(bot.best_move(&game, 9, false), 2);
+        // sqrt(9) children after 9 visits of the root
+        assert_eq!(bot.report(&game).unwrap().children.len(), 3);
+
+        let mut bot = Mcts::builder(game.first_player_turn).progressive_widening(widening).build();
+        assert_eq!(bot.best_move(&game, 1000, false), 2);
+        assert_eq!(bot.report(&game).unwrap().children.len(), game.possible_moves().len());
+
+        let game = pig_hold_to_win();
+        let mut bot = Mcts::builder(game.player_turn)
+            .progressive_widening(widening)
+            .outcome_widening(ProgressiveWidening { k: 1.0, alpha: 0.25 })
+            .build();
+        assert_eq!(bot.best_move(&game, 1000, false), PigMove::Hold);
+    }
+
+    /// the first player can pick up a bonus before the second player wins
+    #[derive(Debug, Clone, Default)]
+    struct Bonus {
+        played: Vec<usize>,
+    }
+
+    impl Game for Bonus {
+        const IS_PERFECT_INFORMATION: bool = true;
+
+        type Move = usize;
+        type GameState = ();
+        type Player = usize;
+
+        fn possible_moves(&self) -> Vec<usize> {
+            match self.played.len() {
+                0 => vec![0, 1],
+                1 => vec![0],
+                _ => Vec::new(),
+            }
+        }
+
+        fn place_move(&mut self, movement: usize) -> anyhow::Result<()> {
+            self.played.push(movement);
+            Ok(())
+        }
+
+        fn score_state(&self, _state: (), player: usize) -> MoveScore {
+            match self.played.as_slice() {
+                [1] if player == 0 => MoveScore::NonTerminal(2.0),
+                [_, _] => MoveScore::Terminal(if player == 1 { 1.0 } else { 0.0 }),
+                _ => MoveScore::None,
+            }
+        }
+
+        fn current_player(&self) -> usize {
+            self.played.len() % 2
+        }
+
+        fn players(&self) -> Vec<usize> {
+            vec![0, 1]
+        }
+    }
+
+    #[test]
+    fn solver() {
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::builder(game.first_player_turn).solver(true).build();
+        assert_eq!(bot.best_move(&game, 1000, false), 2);
+        let report = bot.report(&game).unwrap();
+        assert_eq!(report.proof, Some(Proof::Win(0)));
+        assert!(report.iterations < 1000);
+        let child = report.children.iter().find(|c| c.placement_move == 2).unwrap();
+        assert_eq!(child.proof, Some(Proof::Win(0)));
+
+        let game = tictactoe_forced_block();
+        let mut bot = Mcts::builder(game.first_player_turn).solver(true).build();
+        assert_eq!(bot.best_move(&game, 3000, false), 5);
 
-    fn simulate_uno_win(move_budget: [usize; 2]) -> usize {
+        // the empty board is a draw, with or without transpositions
+        let game = TicTacToe::new();
+        for transpositions in [None, Some(1 << 16)] {
+            let mut builder = Mcts::builder(game.first_player_turn).solver(true);
+            if let Some(size) = transpositions {
+                builder = builder.transpositions(size);
+            }
+            let mut bot = builder.build();
+            let best = bot.best_move(&game, 500_000, false);
+            let report = bot.report(&game).unwrap();
+            assert_eq!(report.proof, Some(Proof::Draw));
+            assert!(report.iterations < 500_000);
+            // proven draws keep their visits, the centre is still the best looking draw
+            let top = report.children.iter().max_by(|a, b| a.mean.total_cmp(&b.mean)).unwrap();
+            assert_eq!(top.placement_move, best);
+        }
+
+        // with the bonus the first player ends ahead, the terminal score alone does not decide it
+        let game = Bonus::default();
+        let mut bot = Mcts::builder(0).solver(true).build();
+        bot.best_move(&game, 100, false);
+        let report = bot.report(&game).unwrap();
+        assert_eq!(report.proof, None);
+        let proof = |m: usize| report.children.iter().find(|c| c.placement_move == m).unwrap().proof;
+        assert_eq!(proof(0), Some(Proof::Win(1)));
+        assert_eq!(proof(1), None);
+    }
+
+    #[test]
+    fn normalized_values() {
+        let bounds = ValueBounds::new(-3.0, 1.0);
+        let stats = ChildStats { score: -2.5, score_sq: 3.25, visits: 2, prior: 1.0 };
+        let normalized = bounds.normalize(stats);
+        assert!((normalized.mean() - 0.4375).abs() < 1e-6);
+        assert!((normalized.variance() - stats.variance() / 16.0).abs() < 1e-6);
+
+        // tictactoe declares its bounds, pig's are observed
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::builder(game.first_player_turn).normalize_values(true).build();
+        assert_eq!(bot.best_move(&game, 1000, false), 2);
+        // means on (-3, 1) reach ucb1 mapped onto [0, 1]
+        let report = bot.report(&game).unwrap();
+        for child in &report.children {
+            let exploration = 2f32.sqrt() * ((report.iterations as f32).ln() / child.visits as f32).sqrt();
+            let mean = child.uct - exploration;
+            assert!((0.0..=1.0).contains(&mean));
+            assert!((mean - (child.mean + 3.0) / 4.0).abs() < 1e-4);
+        }
+
+        let game = tictactoe_forced_block();
+        for backup in [Backup::MaxN, Backup::Paranoid, Backup::Negamax] {
+            let mut bot = Mcts::builder(game.first_player_turn).backup(backup).normalize_values(true).build();
+            assert_eq!(bot.best_move(&game, 3000, false), 5);
+        }
+
+        let game = pig_hold_to_win();
+        let mut bot = Mcts::builder(game.player_turn).normalize_values(true).build();
+        assert_eq!(bot.best_move(&game, 1000, false), PigMove::Hold);
+    }
+
+    #[test]
+    fn rave_search() {
+        let game = tictactoe_one_move_win();
+        let mut bot = Mcts::builder(game.first_player_turn).rave(50.0).build();
+        assert_eq!(bot.best_move(&game, 1000, false), 2);
+
+        // siblings played later in a simulation collect amaf statistics beyond their own visits
+        let amaf = |bot: &Mcts<TicTacToe>| {
+            let mut json = Vec::new();
+            bot.export_json(&mut json).unwrap();
+            let tree: serde_json::Value = serde_json::from_slice(&json).unwrap();
+            tree["nodes"].as_array().unwrap().iter()
+                .zip(tree["stats"].as_array().unwrap())
+                .map(|(n, s)| (n["amaf_visits"].as_u64().unwrap(), s["visits"].as_u64().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        assert!(amaf(&bot).iter().any(|(amaf_visits, visits)| amaf_visits > visits));
+        let mut plain = Mcts::new(game.first_player_turn);
+        plain.best_move(&game, 1000, false);
+        assert!(amaf(&plain).iter().all(|(amaf_visits, _)| *amaf_visits == 0));
+
+        let mut game = Uno::standard_deck(2);
+        let mut bot = Mcts::builder(game.player_turn).information_set(true).rave(50.0).build();
+        let bot_move = bot.best_move(&game, 64, true);
+        assert!(game.possible_moves().contains(&bot_move));
+        game.place_move(bot_move).unwrap();
+    }
+
+    #[test]
+    fn transpositions_share_statistics() {
+        let game = tictactoe_one_move_win();
+        // tiny table so entries get evicted during the search
+        for capacity in [4, 100_000] {
+            let mut bot = Mcts::builder(game.first_player_turn).transpositions(capacity).build();
+            assert_eq!(bot.best_move(&game, 2000, false), 2);
+            assert!(bot.advance(&2));
+        }
+
+        // with a pruning budget a full table prunes the tree, leaving at most the children of
+        // every tracked state
+        let game = TicTacToe::new();
+        let mut bot = Mcts::builder(game.first_player_turn)
+            .transpositions(64)
+            .tree_budget(TreeBudget::Nodes(100_000), BudgetAction::Prune)
+            .build();
+        bot.best_move(&game, 5000, false);
+        assert!(bot.report(&game).unwrap().nodes <= 64 * 9);
+
+        // x on 0 and 2 with o on 1 is reached by two move orders, both nodes share one state
+        // high exploration so both move orders get visited
+        let game = TicTacToe::new();
+        let mut bot = Mcts::builder(game.first_player_turn)
+            .transpositions(100_000)
+            .selection_policy(Ucb1 { c: 10.0 })
+            .build();
+        bot.best_move(&game, 20_000, false);
+        let mut json = Vec::new();
+        bot.export_json(&mut json).unwrap();
+        let tree: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        let canonical = |id: usize| tree["canonical"][id].as_u64().unwrap() as usize;
+        let child = |id: usize, m: u64| {
+            let list = &tree["children"][canonical(id)];
+            let (start, len) = (list["start"].as_u64().unwrap() as usize, list["len"].as_u64().unwrap() as usize);
+            (start..start + len)
+                .map(|e| tree["edges"][e].as_u64().unwrap() as usize)
+                .find(|c| tree["nodes"][*c]["placement_move"] == m)
+                .unwrap()
+        };
+        let a = child(child(child(0, 0), 1), 2);
+        let b = child(child(child(0, 2), 1), 0);
+        assert_ne!(a, b);
+        assert_eq!(canonical(a), canonical(b));
+        let visits = |id: usize| tree["stats"][id]["visits"].as_u64().unwrap();
+        let state_visits = tree["nodes"][canonical(a)]["state"]["visits"].as_u64().unwrap();
+        assert!(visits(a) > 0 && visits(b) > 0);
+        assert!(state_visits > visits(a).max(visits(b)));
+
+        let mut game = TicTacToe::new();
+        let mut bot = Mcts::builder(game.first_player_turn).transpositions(100_000).build();
+        for _ in 0..3 {
+            let m = bot.best_move(&game, 500, false);
+            assert!(game.possible_moves().contains(&m));
+            game.place_move(m).unwrap();
+            bot.advance(&m);
+        }
+    }
+
+    #[test]
+    fn determinize_keeps_observer_view() {
+        let game = Uno::standard_deck(3);
+        let moves = game.possible_moves();
+        for _ in 0..20 {
+            let sampled = game.determinize(game.player_turn, &mut rand::thread_rng());
+            let sampled_moves = sampled.possible_moves();
+            assert_eq!(moves.len(), sampled_moves.len());
+            assert!(moves.iter().all(|m| sampled_moves.contains(m)));
+        }
+    }
+
+    #[test]
+    fn information_set_search_plays_legal_moves() {
         let mut game = Uno::standard_deck(2);
+        let mut bots = [0, 1].map(|p| Mcts::builder(p).information_set(true).build());
+        for _ in 0..30 {
+            let bot_move = bots[game.player_turn].best_move(&game, 16, true);
+            assert!(game.possible_moves().contains(&bot_move));
+            for bot in bots.iter_mut() {
+                bot.advance(&bot_move);
+            }
+            if let uno::GameState::Win = game.place_move(bot_move).unwrap() {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn chance_nodes_hold_winning_total() {
+        for mode in [ChanceMode::Sample, ChanceMode::Enumerate] {
+            let game = pig_hold_to_win();
+            let mut bot = Mcts::builder(game.player_turn).chance_mode(mode).build();
+            assert_eq!(bot.best_move(&game, 1000, false), PigMove::Hold);
+        }
+    }
+
+    #[test]
+    fn chance_nodes_roll_without_turn_total() {
+        let mut game = Pig::new(2, 20);
+        let mut bot = Mcts::new(game.player_turn);
+        assert_eq!(bot.best_move(&game, 2000, false), PigMove::Roll);
+        game.place_move(PigMove::Roll).unwrap();
+        assert!(!game.chance_outcomes().is_empty());
+        game.place_move(PigMove::Face(4)).unwrap();
+        assert_eq!(game.turn_total, 4);
+    }
+
+    /// plays a game from `seed`, returning the winner and every move played
+    fn play_uno(move_budget: [usize; 2], seed: u64) -> (usize, Vec<uno::PlayerMove>) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut game = Uno::standard_deck_seeded(2, rng.gen());
+        let mut bots = [0, 1].map(|p| Mcts::builder(p).information_set(true).seed(rng.gen()).build());
+        let mut played = Vec::new();
 
         loop {
             // println!("{game}");
 
-            let mut bot = Mcts::new(game.player_turn);
+            let bot = &mut bots[game.player_turn];
             let bot_move = if move_budget[game.player_turn] > 0 {
                 bot.best_move(&game, move_budget[game.player_turn], true)
             } else {
-                *game.possible_moves().iter().choose(&mut rand::thread_rng()).unwrap()
+                *game.possible_moves().iter().choose(&mut rng).unwrap()
             };
+            played.push(bot_move);
 
             // println!("player {}: {:?} \n", game.player_turn, bot_move);
 
             // bot.dump_tree();
-            match game.place_move(bot_move).unwrap() {
-                uno::GameState::Win => {
-                    // println!("player {} wins", game.player_turn);
-                    return if game.player_turn == 0 { 1 } else { 0 };
-                },
-                _ => {},
+            for bot in bots.iter_mut() {
+                bot.advance(&bot_move);
+            }
+            if let uno::GameState::Win = game.place_move(bot_move).unwrap() {
+                // println!("player {} wins", game.player_turn);
+                return (game.player_turn, played);
             }
         }
     }
 
+    fn simulate_uno_win(move_budget: [usize; 2], seed: u64) -> usize {
+        let (winner, _) = play_uno(move_budget, seed);
+        if winner == 0 { 1 } else { 0 }
+    }
+
+    #[test]
+    fn seeded_uno_game_replays() {
+        let seed = 3;
+        assert_eq!(play_uno([16, 0], seed), play_uno([16, 0], seed));
+    }
+
+
+    fn iterations_per_sec<G: Game>(game: &G, player: G::Player, iterations: usize, search: impl Fn(&mut Mcts<G>, &G)) -> f32 {
+        let mut bot = Mcts::new(player);
+        let start = Instant::now();
+        search(&mut bot, game);
+        iterations as f32 / start.elapsed().as_secs_f32()
+    }
+
+    fn bench_parallel_throughput<G: Game + Send + Sync>(name: &str, game: &G, player: G::Player, iterations: usize)
+    where
+        G::Move: Send + Sync,
+        G::Player: Send + Sync,
+    {
+        let single = iterations_per_sec(game, player.clone(), iterations, |bot, g| { bot.best_move(g, iterations, true); });
+        println!("{name} best_move: {single:.0} iterations/s");
+        for threads in [1, 2, 4, 8] {
+            let root = iterations_per_sec(game, player.clone(), iterations, |bot, g| { bot.best_move_parallel(g, iterations, threads, true); });
+            let tree = iterations_per_sec(game, player.clone(), iterations, |bot, g| { bot.best_move_tree_parallel(g, iterations, threads, true); });
+            println!("{name} {threads} threads: root parallel {root:.0} iterations/s ({:.2}x), tree parallel {tree:.0} iterations/s ({:.2}x)", root / single, tree / single);
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_parallel_scaling() {
+        bench_parallel_throughput("tictactoe", &TicTacToe::new(), true, 200_000);
+        bench_parallel_throughput("uno", &Uno::standard_deck(2), 0, 4096);
+    }
+
+    /// criterion style throughput, one warm up search followed by `samples` timed searches
+    fn bench_iterations<G: Game>(name: &str, game: &G, iterations: usize, samples: usize, bot: impl Fn() -> Mcts<G>) {
+        let rate = || {
+            let mut bot = bot();
+            let start = Instant::now();
+            bot.best_move(game, iterations, true);
+            iterations as f32 / start.elapsed().as_secs_f32()
+        };
+        rate();
+        let mut rates = (0..samples).map(|_| rate()).collect::<Vec<_>>();
+        rates.sort_by(f32::total_cmp);
+        let mean = rates.iter().sum::<f32>() / samples as f32;
+        println!("{name}: [{:.0} {mean:.0} {:.0}] iterations/s (min mean max)", rates[0], rates[samples - 1]);
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_search_throughput() {
+        bench_iterations("tictactoe", &TicTacToe::new(), 200_000, 5, || Mcts::new(true));
+        // leaves are evaluated without a rollout, so the time goes into walking the tree
+        bench_iterations("tictactoe without rollouts", &TicTacToe::new(), 200_000, 5, || {
+            Mcts::builder(true).evaluator(|_: &TicTacToe| vec![0f32; 2], 0).build()
+        });
+        bench_iterations("uno", &Uno::standard_deck(2), 4096, 10, || Mcts::new(0));
+    }
 
     #[test]
     #[ignore]
@@ -82,7 +1021,7 @@ mod tests {
                 .inspect(|p2| println!("{p1} vs {p2}"))
                 .map(|p2|
                     (0..SAMPLE_SIZE).into_par_iter()
-                        .map(|_| simulate_uno_win([*p1, *p2]))
+                        .map(|_| simulate_uno_win([*p1, *p2], rand::random()))
                         .sum::<usize>()
                 )
                 .collect::<Vec<_>>()
@@ -94,7 +1033,7 @@ mod tests {
         let mut f = File::create("./uno.csv").unwrap();
         for l in scores.iter() {
             let mut s = l.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(",");
-            s.push_str("\n");
+            s.push('\n');
             f.write_all(s.as_bytes()).unwrap();
         }
     }
@@ -0,0 +1,111 @@
+use anyhow::{anyhow, Result};
+
+use crate::game::{Game, MoveScore};
+
+#[derive(Debug, Clone, Copy)]
+pub enum PigState {
+    Win,
+    Continue
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PigMove {
+    #[default]
+    Roll,
+    Hold,
+    // chance outcome of a roll
+    Face(u8),
+}
+
+/// dice game pig: roll to add to the turn total, a one loses it, holding banks it
+#[derive(Clone, Debug)]
+pub(crate) struct Pig {
+    pub scores: Vec<u32>,
+    pub turn_total: u32,
+    pub player_turn: usize,
+    target: u32,
+    rolling: bool,
+}
+
+impl Pig {
+    pub fn new(num_players: usize, target: u32) -> Self {
+        Self {
+            scores: vec![0; num_players],
+            turn_total: 0,
+            player_turn: 0,
+            target,
+            rolling: false,
+        }
+    }
+
+    fn next_player(&mut self) {
+        self.turn_total = 0;
+        self.player_turn = (self.player_turn + 1) % self.scores.len();
+    }
+}
+
+impl Game for Pig {
+    const IS_PERFECT_INFORMATION: bool = true;
+
+    type Move = PigMove;
+    type GameState = PigState;
+    type Player = usize;
+
+    fn possible_moves(&self) -> Vec<Self::Move> {
+        if self.rolling {
+            (1..=6).map(PigMove::Face).collect()
+        } else {
+            vec![PigMove::Roll, PigMove::Hold]
+        }
+    }
+
+    fn place_move(&mut self, movement: Self::Move) -> Result<Self::GameState> {
+        match (self.rolling, movement) {
+            (false, PigMove::Roll) => self.rolling = true,
+            (false, PigMove::Hold) => {
+                self.scores[self.player_turn] += self.turn_total;
+                if self.scores[self.player_turn] >= self.target {
+                    return Ok(PigState::Win);
+                }
+                self.next_player();
+            },
+            (true, PigMove::Face(1)) => {
+                self.rolling = false;
+                self.next_player();
+            },
+            (true, PigMove::Face(n @ 2..=6)) => {
+                self.rolling = false;
+                self.turn_total += n as u32;
+            },
+            _ => return Err(anyhow!("move specified is invalid: {movement:?}")),
+        }
+        Ok(PigState::Continue)
+    }
+
+    fn score_state(&self, state: Self::GameState, player: Self::Player) -> MoveScore {
+        match state {
+            PigState::Win => MoveScore::Terminal(if self.player_turn == player { 1.0 } else { 0.0 }),
+            PigState::Continue => MoveScore::None,
+        }
+    }
+
+    fn current_player(&self) -> Self::Player {
+        self.player_turn
+    }
+
+    fn players(&self) -> Vec<Self::Player> {
+        (0..self.num_players()).collect()
+    }
+
+    fn num_players(&self) -> usize {
+        self.scores.len()
+    }
+
+    fn chance_outcomes(&self) -> Vec<(Self::Move, f32)> {
+        if self.rolling {
+            (1..=6).map(|n| (PigMove::Face(n), 1f32 / 6f32)).collect()
+        } else {
+            Vec::new()
+        }
+    }
+}
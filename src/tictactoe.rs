@@ -1,6 +1,6 @@
 use anyhow::{anyhow, Ok, Result};
 
-use crate::game::{Game, MoveScore};
+use crate::game::{Game, MoveScore, ValueBounds};
 
 #[derive(Debug, Clone, Copy)]
 pub enum WinState {
@@ -53,18 +53,20 @@ impl TicTacToe {
                 v[a] += score(&self.board[b*3+a]);
             }
         }
-        if let Some(score) = h.iter().chain(v.iter()).find(|s| s.abs() == 3) {
+        if h.iter().chain(v.iter()).any(|s| s.abs() == 3) {
             return WinState::Win;
             // should not happen since you cant place a move after the other player has already placed a winning move
         }
 
-        if self.possible_moves().len() == 0 {
+        if self.possible_moves().is_empty() {
             WinState::Draw
         } else {
             WinState::Continue
         }
     }
 
+    /// debugging aid, not called by the tests
+    #[allow(dead_code)]
     pub fn print(&self) {
         for a in (0..9).step_by(3) {
             let s = self.board[a..a+3].iter().map(|m| match m {
@@ -73,7 +75,7 @@ impl TicTacToe {
             }).collect::<String>();
             println!("{s}");
         }
-        println!("");
+        println!();
     }
 }
 
@@ -87,7 +89,8 @@ impl Game for TicTacToe {
     fn possible_moves(&self) -> Vec<Self::Move> {
         self.board.iter()
             .enumerate()
-            .filter_map(|(i, t)| t.is_none().then(|| i))
+            .filter(|(_, t)| t.is_none())
+            .map(|(i, _)| i)
             .collect::<Vec<_>>()
     }
 
@@ -123,4 +126,30 @@ impl Game for TicTacToe {
             _ => MoveScore::None
         }
     }
+
+    fn current_player(&self) -> Self::Player {
+        self.first_player_turn
+    }
+
+    fn value_bounds(&self) -> Option<ValueBounds> {
+        Some(ValueBounds::new(-3f32, 1f32))
+    }
+
+    fn players(&self) -> Vec<Self::Player> {
+        vec![true, false]
+    }
+
+    fn num_players(&self) -> usize {
+        2
+    }
+
+    fn state_hash(&self) -> Option<u64> {
+        // board read as a base 3 number, the player to move is the lowest digit
+        let board = self.board.iter().fold(0u64, |acc, c| acc * 3 + match c {
+            None => 0,
+            Some(true) => 1,
+            Some(false) => 2,
+        });
+        Some(board * 2 + self.first_player_turn as u64)
+    }
 }
@@ -1,9 +1,12 @@
 use core::fmt;
+use std::{collections::hash_map::DefaultHasher, hash::BuildHasherDefault};
 use hashbrown::HashMap;
 use anyhow::{anyhow, Result};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 
 use crate::game::{Game, MoveScore};
 
+#[derive(Clone, Copy)]
 pub(crate) enum GameState {
     Win,
     Continue
@@ -50,8 +53,19 @@ impl PlayerMove {
         }
     }
 
+    #[allow(dead_code)]
     fn is_number(&self) -> bool {
-        if let PlayerMove::Number(_, _) = self { true } else { false }
+        matches!(self, PlayerMove::Number(_, _))
+    }
+
+    /// points of the played card under standard scoring, drawing is worth nothing
+    pub fn points(&self) -> u8 {
+        match self {
+            PlayerMove::Number(_, n) => *n,
+            PlayerMove::Draw(_, _) | PlayerMove::Reverse(_) | PlayerMove::Skip(_) => 20,
+            PlayerMove::Wild(_, _) => 50,
+            PlayerMove::ActionDraw => 0,
+        }
     }
 }
 impl Default for PlayerMove {
@@ -61,28 +75,35 @@ impl Default for PlayerMove {
 }
 
 
-/// uno but all player hands are visible to one another
+/// card counts, hashed with fixed keys so iteration order and with it every draw is reproducible
+type Cards = HashMap<Card, u8, BuildHasherDefault<DefaultHasher>>;
+
+/// uno where every hand is visible to the searcher, unless searched with information set mcts
+/// which redraws the hands of the other players through `determinize`
 #[derive(Debug, Clone)]
 pub(crate) struct Uno {
-    deck: HashMap<Card, u8>,
+    deck: Cards,
     pub player_turn: usize,
-    player_cards: Vec<HashMap<Card, u8>>,
+    player_cards: Vec<Cards>,
     last_play: PlayerMove,
     card_purgatory: Vec<Card>, // card is left in here as part of playing stack, mixed back into deck once cards have run out
     reversed: bool,
-    depth: usize
+    depth: usize,
+    /// draws cards, reseeded by the search for every simulated game
+    rng: StdRng,
 }
 
 impl Uno {
-    pub fn new(deck: HashMap<Card, u8>, num_players: usize, initial_player_cards: usize) -> Self {
+    pub fn new(deck: Cards, num_players: usize, initial_player_cards: usize, seed: u64) -> Self {
         let mut o = Self {
             deck,
             player_turn: 0,
-            player_cards: (0..num_players).map(|_| HashMap::new()).collect(),
+            player_cards: (0..num_players).map(|_| Cards::default()).collect(),
             reversed: false,
             card_purgatory: Vec::new(),
             last_play: PlayerMove::default(),
             depth: 0,
+            rng: StdRng::seed_from_u64(seed),
         };
 
         for player in 0..num_players {
@@ -111,8 +132,8 @@ impl Uno {
         Ok(())
     }
 
-    fn draw_hand(&mut self, cards: usize, number_only: bool) -> Result<HashMap<Card, u8>> {
-        let mut hand = HashMap::new();
+    fn draw_hand(&mut self, cards: usize, number_only: bool) -> Result<Cards> {
+        let mut hand = Cards::default();
         let mut drawn = 0;
         let mut failed_once = false;
         while drawn < cards {
@@ -135,12 +156,12 @@ impl Uno {
         Ok(hand)
     }
 
-    fn random_weighted_card(&self, number_only: bool) -> Option<Card> {
-        let random = rand::random::<f32>();
+    fn random_weighted_card(&mut self, number_only: bool) -> Option<Card> {
+        let random = self.rng.gen::<f32>();
         let valid_cards = self.deck.iter()
             .filter(|(c, n)|
                 **n > 0 &&
-                    (!number_only || match c { Card::Number(_, _) => true, _ => false })
+                    (!number_only || matches!(c, Card::Number(_, _)))
             );
         let total_cards: f32 = valid_cards
             .clone()
@@ -158,7 +179,8 @@ impl Uno {
 
     fn player_card_count(&self, player: usize) -> usize {
         self.player_cards[player].iter()
-            .filter_map(|(_, n)| (*n > 0).then(|| *n as usize))
+            .filter(|(_, n)| **n > 0)
+            .map(|(_, n)| *n as usize)
             .sum()
     }
 
@@ -171,6 +193,11 @@ impl Uno {
     }
 
     pub fn standard_deck(num_players: usize) -> Self {
+        Self::standard_deck_seeded(num_players, rand::random())
+    }
+
+    /// deals the same hands and draws the same cards for equal seeds
+    pub fn standard_deck_seeded(num_players: usize, seed: u64) -> Self {
         let deck = [Colour::Red, Colour::Yellow, Colour::Green, Colour::Blue].iter()
             .flat_map(|colour|
                 (1..=9)
@@ -184,8 +211,8 @@ impl Uno {
                         (Card::Wild(4), 2),
                     ])
             )
-            .collect::<HashMap<_, u8>>();
-        Self::new(deck, num_players, 7)
+            .collect::<Cards>();
+        Self::new(deck, num_players, 7, seed)
     }
 
     fn update_move(&mut self, card: Option<Card>, pmove: PlayerMove) -> Result<bool> {
@@ -200,7 +227,7 @@ impl Uno {
     }
 
     fn next_player(&mut self, scale: u8) {
-        let rev = self.reversed.then(|| -1).unwrap_or(1);
+        let rev = if self.reversed { -1 } else { 1 };
         let num_players = self.player_cards.len() as isize;
         let next_turn = (self.player_turn as isize) + (scale as isize) * (rev as isize);
         let next_turn = ((next_turn % num_players) + num_players) % num_players;
@@ -216,7 +243,6 @@ impl Game for Uno {
     type GameState = GameState;
     type Player = usize;
 
-    // TODO: possibly randomise all other players hands on first move for regular uno
     fn possible_moves(&self) -> Vec<Self::Move> {
         let player_deck = &self.player_cards[self.player_turn];
         let number_cards_in_deck = self.player_card_count(self.player_turn);
@@ -262,7 +288,7 @@ impl Game for Uno {
         }
 
         // disallow hoarding all cards
-        if moves.len() == 0 {
+        if moves.is_empty() {
             moves.push(PlayerMove::ActionDraw);
         }
         moves
@@ -342,6 +368,52 @@ impl Game for Uno {
             // }
         }
     }
+
+    fn current_player(&self) -> Self::Player {
+        self.player_turn
+    }
+
+    fn players(&self) -> Vec<Self::Player> {
+        (0..self.num_players()).collect()
+    }
+
+    fn num_players(&self) -> usize {
+        self.player_cards.len()
+    }
+
+    fn reseed<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        self.rng = StdRng::seed_from_u64(rng.gen());
+    }
+
+    // other hands are dealt again from the cards the observer cannot see, keeping their sizes
+    fn determinize<R: Rng + ?Sized>(&self, observer: Self::Player, rng: &mut R) -> Self {
+        let mut game = self.clone();
+        let mut hidden = Vec::new();
+        let mut hand_sizes = Vec::new();
+        for (player, cards) in game.player_cards.iter_mut().enumerate() {
+            if player == observer {
+                continue;
+            }
+            hand_sizes.push((player, cards.values().map(|n| *n as usize).sum::<usize>()));
+            hidden.extend(cards.drain().flat_map(|(c, n)| (0..n).map(move |_| c)));
+        }
+        for (c, n) in game.deck.iter_mut() {
+            hidden.extend((0..*n).map(|_| *c));
+            *n = 0;
+        }
+
+        hidden.shuffle(rng);
+        let mut hidden = hidden.into_iter();
+        for (player, size) in hand_sizes {
+            for c in hidden.by_ref().take(size) {
+                *game.player_cards[player].entry(c).or_insert(0) += 1;
+            }
+        }
+        for c in hidden {
+            *game.deck.entry(c).or_insert(0) += 1;
+        }
+        game
+    }
 }
 
 impl fmt::Display for Uno {